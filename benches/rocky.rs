@@ -6,21 +6,33 @@ use criterion::Criterion;
 use rocky::vm::VM;
 
 mod examples {
-    use rocky::{cli::RunFileArgs, run_file};
+    use rocky::{
+        cli::RunFileArgs,
+        run_file,
+        vm::{arithmetic::ArithmeticMode, budget::Budget, clock::Clock, memory::HeapLimit},
+    };
 
     use super::*;
 
-    // too long
-    // fn execute_hello_rk(c: &mut Criterion) {
-    //     let clos = || {
-    //         run_file(RunFileArgs {
-    //             num_threads: num_cpus::get(),
-    //             filename: "examples/hello.rk",
-    //         })
-    //     };
+    fn execute_hello_rk(c: &mut Criterion) {
+        let clos = || {
+            run_file(RunFileArgs {
+                num_threads: num_cpus::get(),
+                filename: "examples/hello.rk",
+                debug: false,
+                budget: Budget::unlimited(),
+                heap_limit: HeapLimit::default(),
+                arithmetic_mode: ArithmeticMode::Wrapping,
+                clock: Clock::virtual_clock(),
+                trace: None,
+                resume: false,
+                profile: false,
+                collapsed_stacks: None,
+            })
+        };
 
-    //     c.bench_function("execute_hello_rk", move |b| b.iter(clos));
-    // }
+        c.bench_function("execute_hello_rk", move |b| b.iter(clos));
+    }
 
     fn execute_math_rk(c: &mut Criterion) {
         let clos = || {
@@ -28,6 +40,14 @@ mod examples {
                 num_threads: num_cpus::get(),
                 filename: "examples/math.rk",
                 debug: false,
+                budget: Budget::unlimited(),
+                heap_limit: HeapLimit::default(),
+                arithmetic_mode: ArithmeticMode::Wrapping,
+                clock: Clock::Real,
+                trace: None,
+                resume: false,
+                profile: false,
+                collapsed_stacks: None,
             })
         };
         c.bench_function("execute_math_rk", move |b| b.iter(clos));
@@ -36,7 +56,7 @@ mod examples {
     criterion_group! {
         name = examples;
         config = Criterion::default();
-        targets = execute_math_rk
+        targets = execute_hello_rk, execute_math_rk
     }
 }
 
@@ -46,51 +66,51 @@ mod arithmetic {
     use super::*;
 
     fn execute_add(c: &mut Criterion) {
-        let clos = {
-            let mut test_vm = VM::new();
-            test_vm.registers[0] = 50;
-            test_vm.registers[1] = 25;
-            test_vm.set_program(vec![2, 0, 1, 0], MemoryHeap::new(0));
-            test_vm.run_once();
-        };
-
-        c.bench_function("execute_add", move |b| b.iter(|| clos));
+        c.bench_function("execute_add", |b| {
+            b.iter(|| {
+                let mut test_vm = VM::new();
+                test_vm.registers[0] = 50;
+                test_vm.registers[1] = 25;
+                test_vm.set_program(vec![2, 0, 1, 0], MemoryHeap::new(0));
+                test_vm.run_once();
+            })
+        });
     }
 
     fn execute_sub(c: &mut Criterion) {
-        let clos = {
-            let mut test_vm = VM::new();
-            test_vm.registers[0] = 50;
-            test_vm.registers[1] = 25;
-            test_vm.set_program(vec![3, 0, 1, 0], MemoryHeap::new(0));
-            test_vm.run_once();
-        };
-
-        c.bench_function("execute_sub", move |b| b.iter(|| clos));
+        c.bench_function("execute_sub", |b| {
+            b.iter(|| {
+                let mut test_vm = VM::new();
+                test_vm.registers[0] = 50;
+                test_vm.registers[1] = 25;
+                test_vm.set_program(vec![3, 0, 1, 0], MemoryHeap::new(0));
+                test_vm.run_once();
+            })
+        });
     }
 
     fn execute_mul(c: &mut Criterion) {
-        let clos = {
-            let mut test_vm = VM::new();
-            test_vm.registers[0] = 50;
-            test_vm.registers[1] = 5;
-            test_vm.set_program(vec![4, 0, 1, 0], MemoryHeap::new(0));
-            test_vm.run_once();
-        };
-
-        c.bench_function("execute_mul", move |b| b.iter(|| clos));
+        c.bench_function("execute_mul", |b| {
+            b.iter(|| {
+                let mut test_vm = VM::new();
+                test_vm.registers[0] = 50;
+                test_vm.registers[1] = 5;
+                test_vm.set_program(vec![4, 0, 1, 0], MemoryHeap::new(0));
+                test_vm.run_once();
+            })
+        });
     }
 
     fn execute_div(c: &mut Criterion) {
-        let clos = {
-            let mut test_vm = VM::new();
-            test_vm.registers[0] = 50;
-            test_vm.registers[1] = 6;
-            test_vm.set_program(vec![5, 0, 1, 0], MemoryHeap::new(0));
-            test_vm.run_once();
-        };
-
-        c.bench_function("execute_div", move |b| b.iter(|| clos));
+        c.bench_function("execute_div", |b| {
+            b.iter(|| {
+                let mut test_vm = VM::new();
+                test_vm.registers[0] = 50;
+                test_vm.registers[1] = 6;
+                test_vm.set_program(vec![5, 0, 1, 0], MemoryHeap::new(0));
+                test_vm.run_once();
+            })
+        });
     }
 
     criterion_group! {
@@ -100,4 +120,86 @@ mod arithmetic {
     }
 }
 
-criterion_main!(arithmetic::arithmetic, examples::examples);
+mod heap {
+    use std::ops::Range;
+
+    use criterion::BenchmarkId;
+    use rocky::vm::memory::MemoryHeap;
+
+    use super::*;
+
+    // The layout the heap had before, one buffer that a resize drains and shifts
+    struct ContiguousHeap {
+        data: Vec<u8>,
+        partitions: Vec<Range<usize>>,
+    }
+
+    impl ContiguousHeap {
+        fn new(partitions: usize, size: usize) -> Self {
+            Self {
+                data: vec![0; partitions * size],
+                partitions: (0..partitions).map(|i| i * size..(i + 1) * size).collect(),
+            }
+        }
+
+        fn edit(&mut self, bytes: Vec<u8>, id: usize) {
+            let range = self.partitions[id].clone();
+            self.data.drain(range.clone());
+            for partition in &mut self.partitions {
+                if partition.start >= range.end {
+                    *partition = partition.start - range.len()..partition.end - range.len();
+                }
+            }
+            let start = self.data.len();
+            self.data.extend(bytes);
+            self.partitions[id] = start..self.data.len();
+        }
+    }
+
+    // Resizes the first partition, the others should not make it slower
+    fn edit_resized_partition(c: &mut Criterion) {
+        let mut group = c.benchmark_group("edit_resized_partition");
+        let (short, long) = (vec![1; 8], vec![2; 24]);
+        for partitions in [100, 10_000] {
+            let mut heap = MemoryHeap::new(partitions * 16 + 24);
+            for _ in 0..partitions {
+                heap.add(vec![0; 16]).unwrap();
+            }
+            let mut grow = true;
+            group.bench_with_input(
+                BenchmarkId::new("partitions", partitions),
+                &partitions,
+                |b, _| {
+                    b.iter(|| {
+                        let bytes = if grow { &long } else { &short };
+                        heap.edit(bytes.clone(), 0).unwrap();
+                        grow = !grow;
+                    })
+                },
+            );
+
+            let mut heap = ContiguousHeap::new(partitions, 16);
+            let mut grow = true;
+            group.bench_with_input(
+                BenchmarkId::new("contiguous", partitions),
+                &partitions,
+                |b, _| {
+                    b.iter(|| {
+                        let bytes = if grow { &long } else { &short };
+                        heap.edit(bytes.clone(), 0);
+                        grow = !grow;
+                    })
+                },
+            );
+        }
+        group.finish();
+    }
+
+    criterion_group! {
+        name = heap;
+        config = Criterion::default();
+        targets = edit_resized_partition
+    }
+}
+
+criterion_main!(arithmetic::arithmetic, examples::examples, heap::heap);
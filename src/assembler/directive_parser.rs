@@ -12,15 +12,13 @@ use nom::{
     IResult,
 };
 
-fn directive_declaration<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
+fn directive_declaration(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
     map(preceded(tag("."), alpha1), |name: &str| Token::Directive {
         name: name.to_string(),
     })(i)
 }
 
-fn directive_combined<'a>(
-    i: &'a str,
-) -> IResult<&'a str, AssemblerInstruction, VerboseError<&'a str>> {
+fn directive_combined(i: &str) -> IResult<&str, AssemblerInstruction, VerboseError<&str>> {
     ws(map(
         tuple((
             opt(label_declaration),
@@ -40,7 +38,7 @@ fn directive_combined<'a>(
     ))(i)
 }
 
-pub fn directive<'a>(i: &'a str) -> IResult<&'a str, AssemblerInstruction, VerboseError<&'a str>> {
+pub fn directive(i: &str) -> IResult<&str, AssemblerInstruction, VerboseError<&str>> {
     directive_combined(i)
 }
 
@@ -1,6 +1,8 @@
 use std::error::Error;
 use std::fmt;
 
+use crate::instruction::{Opcode, OperandKind};
+
 #[derive(Debug, Clone)]
 pub enum AssemblerError {
     NoSegmentDeclarationFound { instruction: u32 },
@@ -11,6 +13,22 @@ pub enum AssemblerError {
     InsufficientSections,
     ParseError { error: String },
     UnterminatedProgram,
+    IntegerOutOfRange { value: i64, bits: u8 },
+    UnknownLabel { name: String },
+    UnexpectedFloat { value: f64 },
+    WrongOperandCount { opcode: Opcode, found: usize },
+    WrongOperandKind { opcode: Opcode, position: usize },
+    UnreachableLabel { name: String },
+    ReadOnlyWrite { name: String },
+}
+
+// Padding operands can be left out
+pub fn required_operands(opcode: Opcode) -> usize {
+    opcode
+        .signature()
+        .iter()
+        .filter(|kind| **kind != OperandKind::Padding)
+        .count()
 }
 
 impl fmt::Display for AssemblerError {
@@ -32,6 +50,13 @@ impl fmt::Display for AssemblerError {
             AssemblerError::InsufficientSections => f.write_str("Less than two sections/segments were found in the code"),
             AssemblerError::ParseError { ref error } => f.write_str(&format!("There was an error parsing the code: {}", error)),
             AssemblerError::UnterminatedProgram => f.write_str("The program didnt't end properly"),
+            AssemblerError::IntegerOutOfRange { value, bits } => f.write_str(&format!("The integer {} does not fit in a {}-bit operand", value, bits)),
+            AssemblerError::UnknownLabel { ref name } => f.write_str(&format!("The label {} was never declared", name)),
+            AssemblerError::UnexpectedFloat { value } => f.write_str(&format!("The float {} was used where only integers are allowed", value)),
+            AssemblerError::WrongOperandCount { opcode, found } => f.write_str(&format!("{} takes {} operand(s) but {} were given", opcode.mnemonic(), required_operands(opcode), found)),
+            AssemblerError::WrongOperandKind { opcode, position } => f.write_str(&format!("Operand {} of {} should be {}", position, opcode.mnemonic(), opcode.signature()[position - 1])),
+            AssemblerError::UnreachableLabel { ref name } => f.write_str(&format!("The label {} is out of reach of this jump", name)),
+            AssemblerError::ReadOnlyWrite { ref name } => f.write_str(&format!("{} is declared in .rodata and cannot be written", name)),
         }
     }
 }
@@ -47,6 +72,13 @@ impl Error for AssemblerError {
             AssemblerError::InsufficientSections => "Less than two sections/segments were found in the code",
             AssemblerError::ParseError { .. } => "There was an error parsing the code",
             AssemblerError::UnterminatedProgram => "The program didnt't end properly",
+            AssemblerError::IntegerOutOfRange { .. } => "An integer does not fit in its operand",
+            AssemblerError::UnknownLabel { .. } => "A label was never declared",
+            AssemblerError::UnexpectedFloat { .. } => "A float was used where only integers are allowed",
+            AssemblerError::WrongOperandCount { .. } => "An instruction has the wrong number of operands",
+            AssemblerError::WrongOperandKind { .. } => "An operand is of the wrong kind",
+            AssemblerError::UnreachableLabel { .. } => "A label is out of reach of a jump",
+            AssemblerError::ReadOnlyWrite { .. } => "A read-only partition is written",
         }
     }
 }
@@ -8,11 +8,18 @@ use nom::{
 };
 
 use super::{
-    directive_parser::directive, label_parser::label_declaration, opcode_parser::opcode,
-    operand_parser::operand, symbols::SymbolTable, utils::ws, Token,
+    directive_parser::directive,
+    error::{required_operands, AssemblerError},
+    label_parser::label_declaration,
+    opcode_parser::opcode,
+    operand_parser::operand,
+    symbols::{SymbolTable, SymbolType},
+    utils::ws,
+    Token,
 };
+use crate::instruction::{Opcode, OperandKind};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 pub struct AssemblerInstruction {
     pub opcode: Option<Token>,
     pub label: Option<Token>,
@@ -22,35 +29,77 @@ pub struct AssemblerInstruction {
     pub operand3: Option<Token>,
 }
 
-impl Default for AssemblerInstruction {
-    fn default() -> Self {
-        Self {
-            opcode: None,
-            label: None,
-            directive: None,
-            operand1: None,
-            operand2: None,
-            operand3: None,
-        }
-    }
-}
-
 impl AssemblerInstruction {
-    pub fn to_bytes(&self, symbols: &SymbolTable) -> Vec<u8> {
-        let mut results: Vec<u8> = vec![];
-        if let Some(ref token) = self.opcode {
-            match token {
-                Token::Opcode { code } => results.push(*code as u8),
-                _ => println!("Non-opcode found in opcode field"),
+    // The opcode actually emitted, LOAD and register jumps may switch to another form
+    pub fn resolved_opcode(&self) -> Result<Opcode, AssemblerError> {
+        let code = match self.opcode {
+            Some(Token::Opcode { code: Opcode::IGL }) | None => {
+                return Err(AssemblerError::NonOpcodeInOpcodeField)
+            }
+            Some(Token::Opcode { code }) => code,
+            Some(_) => return Err(AssemblerError::NonOpcodeInOpcodeField),
+        };
+        Ok(match (code, &self.operand1, &self.operand2) {
+            // LOAD only carries 16 unsigned bits, anything else needs the wide encoding
+            (Opcode::LOAD, _, Some(Token::IntegerOperand { value }))
+                if u16::try_from(*value).is_err() =>
+            {
+                Opcode::LOADW
+            }
+            (_, Some(Token::LabelUsage { .. } | Token::IntegerOperand { .. }), _) => {
+                code.with_target().unwrap_or(code)
             }
+            _ => code,
+        })
+    }
+
+    // Number of bytes the instruction assembles to, 0 if it has no valid opcode
+    pub fn size(&self) -> usize {
+        self.resolved_opcode()
+            .map_or(0, |code| 1 + code.operands_size())
+    }
+
+    // `offset` is where the instruction starts, relative to the code start
+    pub fn to_bytes(
+        &self,
+        symbols: &SymbolTable,
+        offset: usize,
+    ) -> Result<Vec<u8>, AssemblerError> {
+        let code = self.resolved_opcode()?;
+
+        let operands = self.operands();
+        let signature = code.signature();
+        if operands.len() < required_operands(code) || operands.len() > signature.len() {
+            return Err(AssemblerError::WrongOperandCount {
+                opcode: code,
+                found: operands.len(),
+            });
         }
-        for operand in &[&self.operand1, &self.operand2, &self.operand3] {
-            if let Some(token) = operand {
-                AssemblerInstruction::extract_operand(token, &mut results, symbols);
+
+        let mut results: Vec<u8> = vec![code as u8];
+        for (position, kind) in signature.iter().enumerate() {
+            match operands.get(position) {
+                Some(token) => AssemblerInstruction::extract_operand(
+                    token,
+                    &mut results,
+                    symbols,
+                    code,
+                    position,
+                    *kind,
+                    offset + self.size(),
+                )?,
+                None => results.push(0),
             }
         }
 
-        results
+        Ok(results)
+    }
+
+    pub fn operands(&self) -> Vec<&Token> {
+        [&self.operand1, &self.operand2, &self.operand3]
+            .into_iter()
+            .flatten()
+            .collect()
     }
 
     pub fn is_label(&self) -> bool {
@@ -59,39 +108,100 @@ impl AssemblerInstruction {
 
     pub fn label_name(&self) -> Option<String> {
         match &self.label {
-            Some(l) => match l {
-                Token::LabelDeclaration { name } => Some(name.clone()),
-                _ => None,
-            },
-            None => None,
+            Some(Token::LabelDeclaration { name }) => Some(name.clone()),
+            _ => None,
         }
     }
 
-    fn extract_operand(t: &Token, results: &mut Vec<u8>, symbols: &SymbolTable) {
-        match t {
-            Token::Register { reg_num } => {
+    fn extract_operand(
+        t: &Token,
+        results: &mut Vec<u8>,
+        symbols: &SymbolTable,
+        opcode: Opcode,
+        position: usize,
+        kind: OperandKind,
+        next: usize,
+    ) -> Result<(), AssemblerError> {
+        let wrong_kind = AssemblerError::WrongOperandKind {
+            opcode,
+            position: position + 1,
+        };
+        match (t, kind) {
+            (Token::Register { reg_num }, OperandKind::Register | OperandKind::Padding) => {
                 results.push(*reg_num);
             }
-            Token::IntegerOperand { value } => {
-                let converted = *value as u16;
-                let byte1 = converted;
-                let byte2 = converted >> 8;
-                results.push(byte2 as u8);
-                results.push(byte1 as u8);
+            (Token::FloatOperand { value }, OperandKind::Float) => {
+                results.extend_from_slice(&value.to_be_bytes());
+            }
+            (Token::IntegerOperand { value }, OperandKind::Float) => {
+                results.extend_from_slice(&(*value as f64).to_be_bytes());
+            }
+            (
+                Token::FloatOperand { value },
+                OperandKind::Short
+                | OperandKind::Wide
+                | OperandKind::HeapIndex
+                | OperandKind::CodeOffset,
+            ) => {
+                return Err(AssemblerError::UnexpectedFloat { value: *value });
             }
-            Token::LabelUsage { name } => {
-                let value = symbols.symbol_value(name).unwrap();
-                let converted = value as u16;
-                let byte1 = converted;
-                let byte2 = converted >> 8;
-                results.push(byte2 as u8);
-                results.push(byte1 as u8);
+            (Token::IntegerOperand { value }, OperandKind::Wide) => {
+                let converted =
+                    i32::try_from(*value).map_err(|_| AssemblerError::IntegerOutOfRange {
+                        value: *value,
+                        bits: 32,
+                    })?;
+                results.extend_from_slice(&converted.to_be_bytes());
             }
-            _ => {
-                println!("Opcode found in operand field");
-                std::process::exit(1);
+            (
+                Token::IntegerOperand { value },
+                OperandKind::Short | OperandKind::HeapIndex | OperandKind::CodeOffset,
+            ) => {
+                let converted =
+                    u16::try_from(*value).map_err(|_| AssemblerError::IntegerOutOfRange {
+                        value: *value,
+                        bits: 16,
+                    })?;
+                results.extend_from_slice(&converted.to_be_bytes());
             }
+            (Token::LabelUsage { name }, OperandKind::Short | OperandKind::HeapIndex) => {
+                let value = symbols
+                    .symbol_value(name)
+                    .ok_or_else(|| AssemblerError::UnknownLabel { name: name.clone() })?;
+                if kind == OperandKind::HeapIndex
+                    && symbols.symbol_type(name) != Some(SymbolType::Data)
+                {
+                    return Err(wrong_kind);
+                }
+                results.extend_from_slice(&(value as u16).to_be_bytes());
+            }
+            (Token::LabelUsage { name }, OperandKind::CodeOffset) => {
+                let target = symbols
+                    .symbol_value(name)
+                    .ok_or_else(|| AssemblerError::UnknownLabel { name: name.clone() })?;
+                if symbols.symbol_type(name) != Some(SymbolType::Label) {
+                    return Err(wrong_kind);
+                }
+                // relative jumps count from the next instruction, in their own direction
+                let value = match opcode {
+                    Opcode::JMPFT => target.checked_sub(next),
+                    Opcode::JMPBT => next.checked_sub(target),
+                    _ => Some(target),
+                };
+                let value = value
+                    .and_then(|value| u16::try_from(value).ok())
+                    .ok_or_else(|| AssemblerError::UnreachableLabel { name: name.clone() })?;
+                results.extend_from_slice(&value.to_be_bytes());
+            }
+            (Token::LabelUsage { name }, OperandKind::Wide) => {
+                let value = symbols
+                    .symbol_value(name)
+                    .ok_or_else(|| AssemblerError::UnknownLabel { name: name.clone() })?;
+                results.extend_from_slice(&(value as i32).to_be_bytes());
+            }
+            _ => return Err(wrong_kind),
         };
+        Ok(())
     }
 
     pub fn is_directive(&self) -> bool {
@@ -100,11 +210,8 @@ impl AssemblerInstruction {
 
     pub fn directive_name(&self) -> Option<String> {
         match &self.directive {
-            Some(d) => match d {
-                Token::Directive { name } => Some(name.clone()),
-                _ => None,
-            },
-            None => None,
+            Some(Token::Directive { name }) => Some(name.clone()),
+            _ => None,
         }
     }
 
@@ -114,21 +221,23 @@ impl AssemblerInstruction {
 
     pub fn string_operand(&self) -> Option<String> {
         match &self.operand1 {
-            Some(d) => match d {
-                Token::RkString { name } => Some(name.clone()),
-                _ => None,
-            },
-            None => None,
+            Some(Token::RkString { name }) => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn float_operand(&self) -> Option<f64> {
+        match &self.operand1 {
+            Some(Token::FloatOperand { value }) => Some(*value),
+            Some(Token::IntegerOperand { value }) => Some(*value as f64),
+            _ => None,
         }
     }
 
-    pub fn integer_operand(&self) -> Option<i32> {
+    pub fn integer_operand(&self) -> Option<i64> {
         match &self.operand1 {
-            Some(d) => match d {
-                Token::IntegerOperand { value } => Some(*value),
-                _ => None,
-            },
-            None => None,
+            Some(Token::IntegerOperand { value }) => Some(*value),
+            _ => None,
         }
     }
 
@@ -137,9 +246,7 @@ impl AssemblerInstruction {
     }
 }
 
-fn instruction_combined<'a>(
-    i: &'a str,
-) -> IResult<&'a str, AssemblerInstruction, VerboseError<&'a str>> {
+fn instruction_combined(i: &str) -> IResult<&str, AssemblerInstruction, VerboseError<&str>> {
     ws(map(
         terminated(
             tuple((
@@ -162,15 +269,12 @@ fn instruction_combined<'a>(
     ))(i)
 }
 
-pub fn instruction<'a>(
-    i: &'a str,
-) -> IResult<&'a str, AssemblerInstruction, VerboseError<&'a str>> {
+pub fn instruction(i: &str) -> IResult<&str, AssemblerInstruction, VerboseError<&str>> {
     alt((instruction_combined, directive))(i)
 }
 
 #[cfg(test)]
 mod test {
-    use super::super::Opcode;
     use super::*;
 
     #[test]
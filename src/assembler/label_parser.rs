@@ -11,7 +11,7 @@ use nom::{
 
 use super::{utils::ws, Token};
 
-pub fn label_declaration<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
+pub fn label_declaration(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
     ws(map(
         terminated(
             many1(alt((alphanumeric1, tag("_")))),
@@ -23,7 +23,7 @@ pub fn label_declaration<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError
     ))(i)
 }
 
-pub fn label_usage<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
+pub fn label_usage(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
     ws(map(
         delimited(
             tag("@"),
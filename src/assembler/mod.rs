@@ -1,6 +1,9 @@
 use byteorder::{LittleEndian, WriteBytesExt};
 
-use crate::{instruction::Opcode, vm::memory::MemoryHeap};
+use crate::{
+    instruction::Opcode,
+    vm::memory::{HeapLimit, MemoryHeap},
+};
 
 use self::{
     error::AssemblerError,
@@ -27,7 +30,8 @@ pub const PIE_HEADER_LENGTH: usize = 64;
 pub enum Token {
     Opcode { code: Opcode },
     Register { reg_num: u8 },
-    IntegerOperand { value: i32 },
+    IntegerOperand { value: i64 },
+    FloatOperand { value: f64 },
     LabelDeclaration { name: String },
     LabelUsage { name: String },
     Directive { name: String },
@@ -52,12 +56,26 @@ pub struct Assembler {
     errors: Vec<AssemblerError>,
 }
 
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Assembler {
     pub fn new() -> Assembler {
         Assembler {
             phase: AssemblerPhase::First,
             symbols: SymbolTable::new(),
-            memory_heap: MemoryHeap::new(0),
+            memory_heap: {
+                // declared data always fits, however long the strings are
+                let mut heap = MemoryHeap::new(0);
+                heap.set_limit(HeapLimit {
+                    max_size: None,
+                    grow: true,
+                });
+                heap
+            },
             bytecode: Vec::new(),
             sections: Vec::new(),
             current_section: None,
@@ -69,7 +87,7 @@ impl Assembler {
     pub fn assemble(&mut self, raw: &str) -> Result<Vec<u8>, Vec<AssemblerError>> {
         match program(raw) {
             Ok((remainder, program)) => {
-                if remainder != "" {
+                if !remainder.is_empty() {
                     println!("Remaining {remainder:?}");
                     return Err(vec![AssemblerError::UnterminatedProgram]);
                 }
@@ -88,6 +106,10 @@ impl Assembler {
 
                 let mut body = self.process_second_phase(&program);
 
+                if !self.errors.is_empty() {
+                    return Err(self.errors.clone());
+                };
+
                 let mut assembled_program = self.write_pie_header();
 
                 // this will empty both body and ro
@@ -105,10 +127,12 @@ impl Assembler {
     }
 
     fn process_first_phase(&mut self, p: &Program) {
+        // code labels resolve to where their instruction starts, relative to the code start
+        let mut offset = 0;
         for i in &p.instructions {
             if i.is_label() {
                 if self.current_section.is_some() {
-                    self.process_label_declaration(&i);
+                    self.process_label_declaration(i, offset);
                 } else {
                     self.errors.push(AssemblerError::NoSegmentDeclarationFound {
                         instruction: self.current_instruction,
@@ -119,6 +143,9 @@ impl Assembler {
             if i.is_directive() {
                 self.process_directive(i);
             }
+            if i.is_opcode() {
+                offset += i.size();
+            }
 
             self.current_instruction += 1;
         }
@@ -126,7 +153,7 @@ impl Assembler {
         self.phase = AssemblerPhase::Second;
     }
 
-    fn process_label_declaration(&mut self, i: &AssemblerInstruction) {
+    fn process_label_declaration(&mut self, i: &AssemblerInstruction, offset: usize) {
         let name = match i.label_name() {
             Some(name) => name,
             None => {
@@ -143,7 +170,12 @@ impl Assembler {
             return;
         }
 
-        let symbol = Symbol::new(name, SymbolType::Label, 0);
+        // data labels get their partition index once the directive is handled
+        let symbol = if i.is_directive() {
+            Symbol::new(name, SymbolType::Data, 0)
+        } else {
+            Symbol::new(name, SymbolType::Label, offset)
+        };
         self.symbols.add_symbol(symbol);
     }
 
@@ -159,22 +191,21 @@ impl Assembler {
         if i.has_operands() {
             match directive_name.as_ref() {
                 "str" => {
-                    if self.phase == AssemblerPhase::First {
-                        self.memory_heap.alloc(256)
-                    };
+                    self.reserve(256);
                     self.handle_str(i);
                 }
                 "int" => {
-                    if self.phase == AssemblerPhase::First {
-                        self.memory_heap.alloc(4)
-                    };
+                    self.reserve(4);
                     self.handle_int(i);
                 }
+                "float" => {
+                    self.reserve(8);
+                    self.handle_float(i);
+                }
                 _ => {
                     self.errors.push(AssemblerError::UnknownDirectiveFound {
                         directive: directive_name.clone(),
                     });
-                    return;
                 }
             }
         } else {
@@ -197,7 +228,7 @@ impl Assembler {
                     }
                 };
 
-                let id = self.memory_heap.add(s.as_bytes().to_vec());
+                let id = self.add_partition(s.as_bytes().to_vec());
 
                 self.symbols.set_symbol_index(&label_name, id)
             }
@@ -222,10 +253,21 @@ impl Assembler {
                     }
                 };
 
+                let int = match i32::try_from(int) {
+                    Ok(int) => int,
+                    Err(_) => {
+                        self.errors.push(AssemblerError::IntegerOutOfRange {
+                            value: int,
+                            bits: 32,
+                        });
+                        return;
+                    }
+                };
+
                 let mut wtr = Vec::new();
                 wtr.write_i32::<LittleEndian>(int).unwrap();
 
-                let id = self.memory_heap.add(wtr);
+                let id = self.add_partition(wtr);
 
                 self.symbols.set_symbol_index(&label_name, id)
             }
@@ -235,6 +277,55 @@ impl Assembler {
         }
     }
 
+    fn handle_float(&mut self, i: &AssemblerInstruction) {
+        if self.phase != AssemblerPhase::First {
+            return;
+        }
+
+        match i.float_operand() {
+            Some(float) => {
+                let label_name = match i.label_name() {
+                    Some(name) => name,
+                    None => {
+                        println!("Found a float with no associated label!");
+                        return;
+                    }
+                };
+
+                let mut wtr = Vec::new();
+                wtr.write_f64::<LittleEndian>(float).unwrap();
+
+                let id = self.add_partition(wtr);
+
+                self.symbols.set_symbol_index(&label_name, id)
+            }
+            None => {
+                println!("Number constant following a .float was empty");
+            }
+        }
+    }
+
+    // Room the program can write into at runtime, on top of its declared data
+    fn reserve(&mut self, bytes: usize) {
+        if self.phase == AssemblerPhase::First {
+            self.memory_heap
+                .alloc(bytes)
+                .expect("the assembler heap has no maximum size");
+        }
+    }
+
+    // Partitions declared in .rodata are flagged read-only in the PIE
+    fn add_partition(&mut self, bytes: Vec<u8>) -> usize {
+        let id = self
+            .memory_heap
+            .add(bytes)
+            .expect("the assembler heap grows as needed");
+        if matches!(self.current_section, Some(AssemblerSection::RoData { .. })) {
+            self.memory_heap.set_read_only(id);
+        }
+        id
+    }
+
     fn process_section_header(&mut self, header_name: &str) {
         let new_section: AssemblerSection = header_name.into();
         if new_section == AssemblerSection::Unknown {
@@ -249,11 +340,7 @@ impl Assembler {
             && self
                 .sections
                 .iter()
-                .find(|s| match s {
-                    AssemblerSection::Data { .. } => true,
-                    _ => false,
-                })
-                .is_some()
+                .any(|s| matches!(s, AssemblerSection::Data { .. }))
             && self.phase == AssemblerPhase::First
         {
             println!("Found a rodata section after a data section");
@@ -269,8 +356,11 @@ impl Assembler {
         let mut program = Vec::new();
         for i in &p.instructions {
             if i.is_opcode() {
-                let mut bytes = i.to_bytes(&self.symbols);
-                program.append(&mut bytes);
+                self.check_read_only_write(i);
+                match i.to_bytes(&self.symbols, program.len()) {
+                    Ok(mut bytes) => program.append(&mut bytes),
+                    Err(error) => self.errors.push(error),
+                }
             }
             if i.is_directive() {
                 self.process_directive(i);
@@ -280,37 +370,60 @@ impl Assembler {
         program
     }
 
+    // Catches the writes to .rodata the VM would fault on, when the partition is known
+    fn check_read_only_write(&mut self, i: &AssemblerInstruction) {
+        let position = match i.resolved_opcode().map(Opcode::written_heap_operand) {
+            Ok(Some(position)) => position,
+            _ => return,
+        };
+        let (index, name) = match i.operands().get(position) {
+            Some(Token::LabelUsage { name })
+                if self.symbols.symbol_type(name) == Some(SymbolType::Data) =>
+            {
+                (self.symbols.symbol_value(name), format!("@{name}"))
+            }
+            Some(Token::IntegerOperand { value }) => {
+                (usize::try_from(*value).ok(), format!("#{value}"))
+            }
+            _ => return,
+        };
+        if index.is_some_and(|index| self.memory_heap.is_read_only(index)) {
+            self.errors.push(AssemblerError::ReadOnlyWrite { name });
+        }
+    }
+
     fn write_pie_header(&self) -> Vec<u8> {
         let mut header = vec![];
         for byte in &PIE_HEADER_PREFIX {
-            header.push(byte.clone());
+            header.push(*byte);
         }
 
         header.append(&mut self.memory_heap.header());
 
         while header.len() < PIE_HEADER_LENGTH {
-            header.push(0 as u8);
+            header.push(0_u8);
         }
 
         header
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub enum AssemblerSection {
-    Data { starting_instruction: Option<u32> },
-    RoData { starting_instruction: Option<u32> },
-    Code { starting_instruction: Option<u32> },
+    Data {
+        starting_instruction: Option<u32>,
+    },
+    RoData {
+        starting_instruction: Option<u32>,
+    },
+    Code {
+        starting_instruction: Option<u32>,
+    },
+    #[default]
     Unknown,
 }
 
-impl Default for AssemblerSection {
-    fn default() -> Self {
-        AssemblerSection::Unknown
-    }
-}
-
-impl<'a> From<&'a str> for AssemblerSection {
+impl From<&str> for AssemblerSection {
     fn from(name: &str) -> AssemblerSection {
         match name {
             "data" => AssemblerSection::Data {
@@ -334,17 +447,145 @@ mod tests {
     use byteorder::ReadBytesExt;
 
     use super::*;
-    use crate::vm::VM;
+    use crate::vm::{events::VMEventType, VM};
 
     #[test]
     fn test_assemble_program() {
         let mut asm = Assembler::new();
-        let test_string = ".data\n.code\nload $0 #100\nload $1 #1\nload $2 #0\ntest: inc $0\nneq $0 $2\njmpe @test\nhlt";
+        let test_string = ".data\n.code\nload $0 #100\nload $1 #1\nload $2 #0\ntest: dec $0\nneq $0 $2\njmpe @test\nhlt";
+        let program = asm.assemble(test_string).unwrap();
+        let mut vm = VM::new();
+        assert_eq!(program.len(), 86);
+        vm.add_bytes(program);
+        assert_eq!(vm.program.len(), 86);
+        let events = vm.run();
+        assert_eq!(vm.registers[0], 0);
+        assert_eq!(
+            events.last().map(|e| e.event().clone()),
+            Some(VMEventType::GracefulStop { code: 0 })
+        );
+    }
+
+    #[test]
+    fn test_assemble_subroutine() {
+        let mut asm = Assembler::new();
+        let test_string =
+            ".data\n.code\nload $0 #11\nload $1 #5\ncall $0\nhlt\npush $1\npop $2\nret";
         let program = asm.assemble(test_string).unwrap();
+        assert_eq!(
+            program[PIE_HEADER_LENGTH..],
+            [1, 0, 0, 11, 1, 1, 0, 5, 28, 0, 0, 26, 1, 27, 2, 29]
+        );
         let mut vm = VM::new();
-        assert_eq!(program.len(), 85);
         vm.add_bytes(program);
-        assert_eq!(vm.program.len(), 85);
+        vm.run();
+        assert_eq!(vm.registers[2], 5);
+    }
+
+    #[test]
+    fn test_assemble_wide_immediates() {
+        let mut asm = Assembler::new();
+        let test_string =
+            ".data\n.code\nload $0 #65535\nload $1 #70000\nload $2 #-1\nload $3 #0x7FFFFFFF\nload $4 #-0b1\nhlt";
+        let program = asm.assemble(test_string).unwrap();
+        assert_eq!(
+            program[PIE_HEADER_LENGTH..PIE_HEADER_LENGTH + 10],
+            [1, 0, 255, 255, 42, 1, 0, 1, 17, 112]
+        );
+        let mut vm = VM::new();
+        vm.add_bytes(program);
+        vm.run();
+        assert_eq!(vm.registers[..5], [65535, 70000, -1, i32::MAX, -1]);
+    }
+
+    #[test]
+    fn test_reject_out_of_range_integers() {
+        let mut asm = Assembler::new();
+        let result = asm.assemble(".data\n.code\nload $0 #0x80000000\nhlt");
+        assert!(matches!(
+            result.unwrap_err()[..],
+            [AssemblerError::IntegerOutOfRange {
+                value: 0x80000000,
+                bits: 32
+            }]
+        ));
+
+        let mut asm = Assembler::new();
+        let result = asm.assemble(".data\nbig: .int #-2147483649\n.code\nhlt");
+        assert!(matches!(
+            result.unwrap_err()[..],
+            [AssemblerError::IntegerOutOfRange { bits: 32, .. }]
+        ));
+
+        let mut asm = Assembler::new();
+        let result = asm.assemble(".data\n.code\nprts #70000\nhlt");
+        assert!(matches!(
+            result.unwrap_err()[..],
+            [AssemblerError::IntegerOutOfRange { bits: 16, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_assemble_floats() {
+        let mut asm = Assembler::new();
+        let test_string = ".data\nratio: .float #0.5\n.code\nloadf $0 #1.5\nloadf $1 #2\naddf $0 $1 $2\nftoi $2 $3\nldf @ratio $4\nhlt";
+        let program = asm.assemble(test_string).unwrap();
+        assert_eq!(asm.memory_heap.get(0), Some(0.5f64.to_le_bytes().to_vec()));
+        let mut vm = VM::new();
+        vm.add_bytes(program);
+        vm.run();
+        assert_eq!(vm.float_registers[..3], [1.5, 2.0, 3.5]);
+        assert_eq!(vm.registers[3], 3);
+        assert_eq!(vm.float_registers[4], 0.5);
+
+        let mut asm = Assembler::new();
+        let result = asm.assemble(".data\n.code\nload $0 #1.5\nhlt");
+        assert!(matches!(
+            result.unwrap_err()[..],
+            [AssemblerError::UnexpectedFloat { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_validate_operands() {
+        let mut asm = Assembler::new();
+        let result = asm.assemble(".data\n.code\nadd $0 $1\nhlt");
+        assert!(matches!(
+            result.unwrap_err()[..],
+            [AssemblerError::WrongOperandCount {
+                opcode: Opcode::ADD,
+                found: 2
+            }]
+        ));
+
+        let mut asm = Assembler::new();
+        let result = asm.assemble(".data\n.code\nprts $3\nhlt $0");
+        assert!(matches!(
+            result.unwrap_err()[..],
+            [
+                AssemblerError::WrongOperandKind {
+                    opcode: Opcode::PRTS,
+                    position: 1
+                },
+                AssemblerError::WrongOperandCount {
+                    opcode: Opcode::HLT,
+                    ..
+                }
+            ]
+        ));
+
+        assert_eq!(
+            AssemblerError::WrongOperandKind {
+                opcode: Opcode::PRTS,
+                position: 1
+            }
+            .to_string(),
+            "Operand 1 of prts should be a heap index"
+        );
+
+        let mut asm = Assembler::new();
+        let program = asm.assemble(".data\n.code\neq $0 $1\nlt $0 $1 $2").unwrap();
+        assert_eq!(program[PIE_HEADER_LENGTH..], [9, 0, 1, 0, 12, 0, 1, 2]);
     }
 
     #[test]
@@ -355,7 +596,133 @@ mod tests {
         assert_eq!(program.is_ok(), true);
         let mut rdr = Cursor::new(program.unwrap());
         rdr.set_position(PIE_HEADER_PREFIX.len() as u64);
-        assert_eq!(rdr.read_u32::<LittleEndian>().unwrap(), 4);
         assert_eq!(rdr.read_u32::<LittleEndian>().unwrap(), 5);
+        assert_eq!(rdr.read_u32::<LittleEndian>().unwrap(), 5);
+        assert_eq!(asm.symbols.symbol_value("test"), Some(12));
+    }
+
+    #[test]
+    fn test_assemble_int_variables() {
+        let mut asm = Assembler::new();
+        let test_string =
+            ".data\ncounter: .int #41\n.code\nldi @counter $0\ninc $0\nsti $0 @counter\nhlt";
+        let program = asm.assemble(test_string).unwrap();
+        let mut vm = VM::new();
+        vm.add_bytes(program);
+        vm.run();
+        assert_eq!(vm.registers[0], 42);
+        assert_eq!(vm.memory_heap.get(0), Some(42i32.to_le_bytes().to_vec()));
+    }
+
+    #[test]
+    fn test_code_label_offsets() {
+        let mut asm = Assembler::new();
+        let test_string = ".data
+hello: .str 'Hi'
+.code
+start: load $0 #70000
+loop: loadf $1 #1
+dec $0
+end: hlt";
+        asm.assemble(test_string).unwrap();
+        assert_eq!(asm.symbols.symbol_value("start"), Some(0));
+        assert_eq!(asm.symbols.symbol_value("loop"), Some(6));
+        assert_eq!(asm.symbols.symbol_value("end"), Some(18));
+        assert_eq!(asm.symbols.symbol_value("hello"), Some(0));
+    }
+
+    #[test]
+    fn test_assemble_label_jumps() {
+        let mut asm = Assembler::new();
+        let test_string = ".data
+.code
+load $0 #3
+jmp @loop
+skipped: inc $1
+loop: inc $2
+call @double
+dec $0
+neq $0 $3
+jmpe @loop
+jmpf @end
+hlt
+end: jmpb @exit
+exit: hlt
+double: add $2 $2 $2
+ret";
+        let program = asm.assemble(test_string).unwrap();
+        let mut vm = VM::new();
+        vm.add_bytes(program);
+        let events = vm.run();
+        assert_eq!(
+            events.last().map(|e| e.event().clone()),
+            Some(VMEventType::GracefulStop { code: 0 })
+        );
+        assert_eq!(vm.registers[0], 0);
+        assert_eq!(vm.registers[1], 0);
+        assert_eq!(vm.registers[2], 14);
+    }
+
+    #[test]
+    fn test_reject_rodata_writes() {
+        let mut asm = Assembler::new();
+        let result = asm.assemble(
+            ".rodata
+prompt: .str 'Name? '
+.data
+name: .str ''
+.code
+asks @prompt @name
+asks @prompt @prompt
+grps @name @prompt #0",
+        );
+        let errors = result.unwrap_err();
+        let names: Vec<&str> = errors
+            .iter()
+            .map(|error| match error {
+                AssemblerError::ReadOnlyWrite { name } => name.as_str(),
+                error => panic!("Expected a read-only write, got {error:?}"),
+            })
+            .collect();
+        assert_eq!(names, vec!["@prompt", "#0"]);
+        assert!(asm.memory_heap.is_read_only(0));
+        assert!(!asm.memory_heap.is_read_only(1));
+    }
+
+    #[test]
+    fn test_reject_bad_jump_labels() {
+        let mut asm = Assembler::new();
+        let result = asm.assemble(
+            ".data
+.code
+start: hlt
+jmpf @start",
+        );
+        assert!(matches!(
+            &result.unwrap_err()[..],
+            [AssemblerError::UnreachableLabel { name }] if name == "start"
+        ));
+
+        let mut asm = Assembler::new();
+        let result = asm.assemble(
+            ".data
+hello: .str 'Hi'
+.code
+start: jmp @hello
+prts @start",
+        );
+        assert!(matches!(
+            result.unwrap_err()[..],
+            [
+                AssemblerError::WrongOperandKind {
+                    opcode: Opcode::JMPT,
+                    position: 1
+                },
+                AssemblerError::WrongOperandKind {
+                    opcode: Opcode::PRTS,
+                    position: 1
+                }
+            ]
+        ));
     }
 }
@@ -2,7 +2,7 @@ use super::Token;
 use crate::instruction::Opcode;
 use nom::{character::complete::alpha1, combinator::map, error::VerboseError, IResult};
 
-pub fn opcode<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
+pub fn opcode(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
     map(alpha1, |code: &str| Token::Opcode {
         code: Opcode::from(code.to_lowercase()),
     })(i)
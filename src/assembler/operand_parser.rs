@@ -1,27 +1,69 @@
 use super::{label_parser::label_usage, register_parser::register, utils::ws, Token};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until},
-    character::complete::digit1,
-    combinator::map,
+    bytes::complete::{tag, tag_no_case, take_until, take_while1},
+    character::complete::{digit1, hex_digit1},
+    combinator::{map, map_res, opt, recognize},
     error::VerboseError,
-    sequence::{delimited, preceded},
+    sequence::{delimited, preceded, tuple},
     IResult,
 };
 
-pub fn operand<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
-    ws(alt((integer_operand, rkstring, register, label_usage)))(i)
+pub fn operand(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
+    ws(alt((
+        float_operand,
+        integer_operand,
+        rkstring,
+        register,
+        label_usage,
+    )))(i)
 }
 
-fn integer_operand<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
-    map(preceded(tag("#"), digit1), |reg_num: &str| {
-        Token::IntegerOperand {
-            value: reg_num.parse::<i32>().unwrap(),
-        }
-    })(i)
+// Floats are told apart from integers by their decimal point: #1.5, #-0.25
+fn float_operand(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
+    map_res(
+        preceded(
+            tag("#"),
+            recognize(tuple((opt(tag("-")), digit1, tag("."), digit1))),
+        ),
+        |float: &str| {
+            float
+                .parse::<f64>()
+                .map(|value| Token::FloatOperand { value })
+        },
+    )(i)
+}
+
+// Accepts decimal, hexadecimal (#0xFF) and binary (#0b101) literals, all of which can be negative.
+// Whether the value fits the operand is checked by the assembler.
+fn integer_operand(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
+    map(
+        preceded(
+            tag("#"),
+            tuple((
+                opt(tag("-")),
+                alt((
+                    map_res(preceded(tag_no_case("0x"), hex_digit1), |digits| {
+                        i64::from_str_radix(digits, 16)
+                    }),
+                    map_res(
+                        preceded(
+                            tag_no_case("0b"),
+                            take_while1(|c: char| c == '0' || c == '1'),
+                        ),
+                        |digits| i64::from_str_radix(digits, 2),
+                    ),
+                    map_res(digit1, |digits: &str| digits.parse::<i64>()),
+                )),
+            )),
+        ),
+        |(sign, value)| Token::IntegerOperand {
+            value: if sign.is_some() { -value } else { value },
+        },
+    )(i)
 }
 
-fn rkstring<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
+fn rkstring(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
     map(
         alt((
             delimited(tag("'"), take_until("'"), tag("'")),
@@ -49,6 +91,39 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_integer_operand_formats() {
+        let value = |i| integer_operand(i).map(|(_, token)| token);
+        assert_eq!(value("#-1"), Ok(Token::IntegerOperand { value: -1 }));
+        assert_eq!(value("#0xFF"), Ok(Token::IntegerOperand { value: 255 }));
+        assert_eq!(value("#0Xff"), Ok(Token::IntegerOperand { value: 255 }));
+        assert_eq!(value("#-0x10"), Ok(Token::IntegerOperand { value: -16 }));
+        assert_eq!(value("#0b101"), Ok(Token::IntegerOperand { value: 5 }));
+        assert_eq!(
+            value("#4294967296"),
+            Ok(Token::IntegerOperand { value: 1 << 32 })
+        );
+        assert!(value("#-").is_err());
+    }
+
+    #[test]
+    fn test_parse_float_operand() {
+        assert_eq!(
+            float_operand("#1.5"),
+            Ok(("", Token::FloatOperand { value: 1.5 }))
+        );
+        assert_eq!(
+            float_operand("#-0.25"),
+            Ok(("", Token::FloatOperand { value: -0.25 }))
+        );
+        assert!(float_operand("#1").is_err());
+        assert!(float_operand("#1.").is_err());
+        assert_eq!(
+            operand("#3 ").map(|(_, token)| token),
+            Ok(Token::IntegerOperand { value: 3 })
+        );
+    }
+
     #[test]
     fn test_parse_string_operand() {
         assert!(rkstring("'This is a test'").is_ok());
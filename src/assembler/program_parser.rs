@@ -1,6 +1,7 @@
 use nom::{combinator::map, error::VerboseError, multi::many1, IResult};
 
 use super::{
+    error::AssemblerError,
     instruction_parser::{instruction, AssemblerInstruction},
     symbols::SymbolTable,
 };
@@ -11,15 +12,15 @@ pub struct Program {
 }
 
 impl Program {
-    pub fn to_bytes(&self, symbols: &SymbolTable) -> Vec<u8> {
+    pub fn to_bytes(&self, symbols: &SymbolTable) -> Result<Vec<u8>, AssemblerError> {
         let mut program = vec![];
         for instruction in &self.instructions {
-            program.append(&mut instruction.to_bytes(symbols));
+            program.append(&mut instruction.to_bytes(symbols, program.len())?);
         }
-        program
+        Ok(program)
     }
 }
-pub fn program<'a>(i: &'a str) -> IResult<&'a str, Program, VerboseError<&'a str>> {
+pub fn program(i: &str) -> IResult<&str, Program, VerboseError<&str>> {
     map(
         many1(instruction),
         |instructions: Vec<AssemblerInstruction>| Program { instructions },
@@ -44,7 +45,7 @@ mod test {
         let result = program("load $0 #100\n");
         assert_eq!(result.is_ok(), true);
         let (_, program) = result.unwrap();
-        let bytecode = program.to_bytes(&SymbolTable::new());
+        let bytecode = program.to_bytes(&SymbolTable::new()).unwrap();
         assert_eq!(bytecode.len(), 4);
         println!("{:?}", bytecode);
     }
@@ -4,7 +4,7 @@ use nom::{
     sequence::preceded, IResult,
 };
 
-pub fn register<'a>(i: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
+pub fn register(i: &str) -> IResult<&str, Token, VerboseError<&str>> {
     map(preceded(tag("$"), digit1), |reg_num: &str| {
         Token::Register {
             reg_num: reg_num.parse::<u8>().unwrap(),
@@ -16,9 +16,11 @@ impl Symbol {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SymbolType {
     Label,
+    // A label naming a heap partition, its index is the partition's
+    Data,
 }
 
 #[derive(Debug)]
@@ -26,6 +28,12 @@ pub struct SymbolTable {
     pub symbols: Vec<Symbol>,
 }
 
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SymbolTable {
     pub fn new() -> SymbolTable {
         SymbolTable {
@@ -46,6 +54,29 @@ impl SymbolTable {
         None
     }
 
+    pub fn symbol_type(&self, s: &str) -> Option<SymbolType> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.name == s)
+            .map(|symbol| symbol.symbol_type)
+    }
+
+    pub fn symbol_name(&self, index: usize, symbol_type: SymbolType) -> Option<&str> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.index == index && symbol.symbol_type == symbol_type)
+            .map(|symbol| symbol.name.as_str())
+    }
+
+    // The code label with the closest offset at or before this one
+    pub fn enclosing_label(&self, offset: usize) -> Option<&str> {
+        self.symbols
+            .iter()
+            .filter(|symbol| symbol.symbol_type == SymbolType::Label && symbol.index <= offset)
+            .max_by_key(|symbol| symbol.index)
+            .map(|symbol| symbol.name.as_str())
+    }
+
     pub fn has_symbol(&self, s: &str) -> bool {
         for symbol in &self.symbols {
             if symbol.name == s {
@@ -81,4 +112,26 @@ mod test {
         let v = sym.symbol_value("does_not_exist");
         assert_eq!(v.is_some(), false);
     }
+
+    #[test]
+    fn test_symbol_name() {
+        let mut sym = SymbolTable::new();
+        sym.add_symbol(Symbol::new("start".to_string(), SymbolType::Label, 0));
+        sym.add_symbol(Symbol::new("hello".to_string(), SymbolType::Data, 0));
+        assert_eq!(sym.symbol_name(0, SymbolType::Data), Some("hello"));
+        assert_eq!(sym.symbol_name(0, SymbolType::Label), Some("start"));
+        assert_eq!(sym.symbol_name(1, SymbolType::Data), None);
+    }
+
+    #[test]
+    fn test_enclosing_label() {
+        let mut sym = SymbolTable::new();
+        sym.add_symbol(Symbol::new("start".to_string(), SymbolType::Label, 0));
+        sym.add_symbol(Symbol::new("hello".to_string(), SymbolType::Data, 6));
+        sym.add_symbol(Symbol::new("loop".to_string(), SymbolType::Label, 8));
+        assert_eq!(sym.enclosing_label(4), Some("start"));
+        assert_eq!(sym.enclosing_label(8), Some("loop"));
+        assert_eq!(sym.enclosing_label(30), Some("loop"));
+        assert_eq!(SymbolTable::new().enclosing_label(0), None);
+    }
 }
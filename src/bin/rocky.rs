@@ -2,13 +2,16 @@ extern crate rocky;
 
 use clap::{parser::RawValues, ArgMatches};
 use rocky::{
-    cli::{cli, AddSshKeyArgs, Args, REPLArgs, RunFileArgs},
+    cli::{cli, AddSshKeyArgs, Args, DisassembleArgs, REPLArgs, RunFileArgs},
+    disassemble_file,
     repl::REPLMode,
     run_file,
     ssh::start_ssh_server,
     start_repl,
+    vm::{budget::Budget, history::DEFAULT_HISTORY_CAP, memory::HeapLimit},
 };
 use rustyline::error::ReadlineError;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<(), ReadlineError> {
@@ -24,6 +27,7 @@ async fn main() -> Result<(), ReadlineError> {
             }
             start_repl(args)?
         }
+        Args::Disassemble(args) => disassemble_file(args),
         Args::AddSshKey(args) => {
             println!("User tried to add SSH key at {}!", args.pub_key_file)
         }
@@ -32,6 +36,36 @@ async fn main() -> Result<(), ReadlineError> {
     Ok(())
 }
 
+fn get_budget(args: &ArgMatches) -> Budget {
+    let parse = |name: &str, description: &str| {
+        unwrap(args.get_raw(name)).and_then(|value| match value.parse::<u64>() {
+            Ok(v) => Some(v),
+            Err(_e) => {
+                println!("Invalid argument for {description}: {value}. Ignoring it.");
+                None
+            }
+        })
+    };
+    Budget {
+        max_instructions: parse("max_instructions", "maximum instructions"),
+        timeout: parse("timeout", "timeout").map(Duration::from_millis),
+    }
+}
+
+fn get_heap_limit(args: &ArgMatches) -> HeapLimit {
+    HeapLimit {
+        max_size: unwrap(args.get_raw("max_heap")).and_then(|value| {
+            value
+                .parse::<usize>()
+                .map_err(|_e| {
+                    println!("Invalid argument for maximum heap size: {value}. Ignoring it.")
+                })
+                .ok()
+        }),
+        grow: args.get_flag("grow_heap"),
+    }
+}
+
 fn get_arguments<'a>(matches: &'a ArgMatches) -> Args<'a> {
     let (command, args) = matches.subcommand().map_or(("rocky", matches), |s| s);
     match command {
@@ -52,6 +86,20 @@ fn get_arguments<'a>(matches: &'a ArgMatches) -> Args<'a> {
                 },
                 filename: input_file,
                 debug: args.get_flag("debug"),
+                resume: args.get_flag("resume"),
+                profile: args.get_flag("profile"),
+                collapsed_stacks: unwrap(args.get_raw("collapsed_stacks")),
+                trace: unwrap(args.get_raw("trace")).and_then(|format| format.parse().ok()),
+                budget: get_budget(args),
+                heap_limit: get_heap_limit(args),
+                arithmetic_mode: unwrap(args.get_raw("overflow"))
+                    .unwrap()
+                    .parse()
+                    .unwrap_or_default(),
+                clock: unwrap(args.get_raw("clock"))
+                    .unwrap()
+                    .parse()
+                    .unwrap_or_default(),
             }),
             None => Args::Repl(REPLArgs {
                 mode: {
@@ -66,15 +114,35 @@ fn get_arguments<'a>(matches: &'a ArgMatches) -> Args<'a> {
                     .unwrap()
                     .parse()
                     .unwrap_or_else(|_| panic!("Invalid Port")),
+                budget: get_budget(args),
+                heap_limit: get_heap_limit(args),
+                arithmetic_mode: unwrap(args.get_raw("overflow"))
+                    .unwrap()
+                    .parse()
+                    .unwrap_or_default(),
+                clock: unwrap(args.get_raw("clock"))
+                    .unwrap()
+                    .parse()
+                    .unwrap_or_default(),
+                history_cap: match unwrap(args.get_raw("history")) {
+                    Some(count) => count.parse::<usize>().unwrap_or_else(|_| {
+                        println!("Invalid argument for history: {count}. Using default.");
+                        DEFAULT_HISTORY_CAP
+                    }),
+                    None => DEFAULT_HISTORY_CAP,
+                },
             }),
         },
         "add-ssh-key" => Args::AddSshKey(AddSshKeyArgs {
             pub_key_file: unwrap(args.get_raw("pub_key_file")).unwrap(),
         }),
+        "disassemble" => Args::Disassemble(DisassembleArgs {
+            filename: unwrap(args.get_raw("input_file")).unwrap(),
+        }),
         _ => panic!("Invalid Command \"{command}\""),
     }
 }
 
-fn unwrap(raw: Option<RawValues>) -> Option<&str> {
+fn unwrap(raw: Option<RawValues<'_>>) -> Option<&str> {
     raw.map(|mut v| v.next().unwrap().to_str().unwrap())
 }
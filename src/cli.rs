@@ -1,6 +1,12 @@
 use clap::{command, Arg, ArgAction, Command};
 
-use crate::repl::REPLMode;
+use crate::{
+    repl::REPLMode,
+    vm::{
+        arithmetic::ArithmeticMode, budget::Budget, clock::Clock, memory::HeapLimit,
+        trace::TraceFormat,
+    },
+};
 
 pub fn cli() -> Command {
     command!()
@@ -22,12 +28,75 @@ pub fn cli() -> Command {
                 .alias("verbose")
                 .short_alias('v')
                 .action(ArgAction::SetTrue),
+            Arg::new("resume")
+                .help("Treat INPUT_FILE as a VM snapshot and resume it")
+                .required(false)
+                .long("resume")
+                .short('r')
+                .action(ArgAction::SetTrue),
             Arg::new("threads")
                 .help("Number of OS threads the VM will utilize")
                 .required(false)
                 .long("threads")
                 .short('t')
                 .value_name("threads"),
+            Arg::new("max_instructions")
+                .help("Stop programs after executing this many instructions")
+                .required(false)
+                .long("max-instructions")
+                .alias("fuel")
+                .value_name("COUNT"),
+            Arg::new("timeout")
+                .help("Stop programs after running for this many milliseconds")
+                .required(false)
+                .long("timeout")
+                .value_name("MILLISECONDS"),
+            Arg::new("max_heap")
+                .help("Never let the heap hold more than this many bytes")
+                .required(false)
+                .long("max-heap")
+                .value_name("BYTES"),
+            Arg::new("grow_heap")
+                .help("Grow the heap when a write does not fit, up to --max-heap")
+                .required(false)
+                .long("grow-heap")
+                .action(ArgAction::SetTrue),
+            Arg::new("overflow")
+                .help("What integer arithmetic does when it overflows")
+                .required(false)
+                .long("overflow")
+                .value_parser(["wrapping", "saturating", "trapping"])
+                .default_value("wrapping"),
+            Arg::new("clock")
+                .help("Whether SLP and SLPS really sleep or advance a virtual clock")
+                .required(false)
+                .long("clock")
+                .value_parser(["real", "virtual"])
+                .default_value("real"),
+            Arg::new("trace")
+                .help("Log every executed instruction to stderr, as human readable lines or JSON lines")
+                .required(false)
+                .long("trace")
+                .value_parser(["human", "json"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("human")
+                .value_name("FORMAT"),
+            Arg::new("history")
+                .help("How many instructions the REPL remembers to step back through")
+                .required(false)
+                .long("history")
+                .value_name("COUNT"),
+            Arg::new("profile")
+                .help("Report where the program spent its instructions and time once it stops")
+                .required(false)
+                .long("profile")
+                .action(ArgAction::SetTrue),
+            Arg::new("collapsed_stacks")
+                .help("Profile the program and write its call stacks for flamegraph tools")
+                .required(false)
+                .long("collapsed-stacks")
+                .value_name("FILE"),
             Arg::new("hexadecimal")
                 .help("Use the REPL in hexadecimal (for you, little weirdo)")
                 .required(false)
@@ -64,6 +133,18 @@ pub fn cli() -> Command {
                     .index(1)
                     .value_name("PUB_KEY_FILE")]),
         )
+        .subcommand(
+            command!()
+                .name("disassemble")
+                .about("Prints the assembly of a .rk file or of assembled bytecode")
+                .version("0.0.1")
+                .author("Galitan-dev <galitan.dev@gmail.com>")
+                .args([Arg::new("input_file")
+                    .help("Path to the .rk file or bytecode to disassemble")
+                    .required(true)
+                    .index(1)
+                    .value_name("INPUT_FILE")]),
+        )
 }
 
 #[derive(Debug, Clone)]
@@ -71,6 +152,7 @@ pub enum Args<'a> {
     Repl(REPLArgs),
     RunFile(RunFileArgs<'a>),
     AddSshKey(AddSshKeyArgs<'a>),
+    Disassemble(DisassembleArgs<'a>),
 }
 
 #[derive(Debug, Clone)]
@@ -78,6 +160,11 @@ pub struct REPLArgs {
     pub mode: REPLMode,
     pub enable_ssh: bool,
     pub ssh_port: u8,
+    pub budget: Budget,
+    pub heap_limit: HeapLimit,
+    pub arithmetic_mode: ArithmeticMode,
+    pub clock: Clock,
+    pub history_cap: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -85,9 +172,22 @@ pub struct RunFileArgs<'a> {
     pub num_threads: usize,
     pub filename: &'a str,
     pub debug: bool,
+    pub budget: Budget,
+    pub heap_limit: HeapLimit,
+    pub arithmetic_mode: ArithmeticMode,
+    pub clock: Clock,
+    pub trace: Option<TraceFormat>,
+    pub resume: bool,
+    pub profile: bool,
+    pub collapsed_stacks: Option<&'a str>,
 }
 
 #[derive(Debug, Clone)]
 pub struct AddSshKeyArgs<'a> {
     pub pub_key_file: &'a str,
 }
+
+#[derive(Debug, Clone)]
+pub struct DisassembleArgs<'a> {
+    pub filename: &'a str,
+}
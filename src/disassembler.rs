@@ -0,0 +1,238 @@
+use std::{
+    error::Error,
+    fmt::{self, Write},
+    io::Cursor,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{
+    assembler::{
+        symbols::{SymbolTable, SymbolType},
+        PIE_HEADER_LENGTH, PIE_HEADER_PREFIX,
+    },
+    instruction::{Opcode, Operand},
+    vm::memory::{MemoryHeap, PARTITION_ENTRY_SIZE},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisassemblerError {
+    InvalidHeader,
+}
+
+impl fmt::Display for DisassemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisassemblerError::InvalidHeader => f.write_str("The program header was incorrect"),
+        }
+    }
+}
+
+impl Error for DisassemblerError {}
+
+// Turns a PIE program back into assembly: the heap partitions first, then one
+// instruction per line prefixed with its code offset. Heap indices and jump targets
+// are shown as labels when a symbol table is given.
+pub fn disassemble(
+    program: &[u8],
+    symbols: Option<&SymbolTable>,
+) -> Result<String, DisassemblerError> {
+    if program.len() < PIE_HEADER_LENGTH || program[..PIE_HEADER_PREFIX.len()] != PIE_HEADER_PREFIX
+    {
+        return Err(DisassemblerError::InvalidHeader);
+    }
+
+    let header = &program[PIE_HEADER_PREFIX.len()..PIE_HEADER_PREFIX.len() + 12];
+    let heap = MemoryHeap::from_bytes(
+        &mut Cursor::new(&program[PIE_HEADER_LENGTH..]),
+        &mut Cursor::new(header),
+    )
+    .map_err(|_| DisassemblerError::InvalidHeader)?;
+    let mut rdr = Cursor::new(header);
+    let table_size = rdr.read_u32::<LittleEndian>().unwrap() as usize;
+    let partitions = table_size / PARTITION_ENTRY_SIZE;
+    let data_size = rdr.read_u32::<LittleEndian>().unwrap() as usize;
+    let code_start = PIE_HEADER_LENGTH + table_size + data_size;
+    let code = program
+        .get(code_start..)
+        .ok_or(DisassemblerError::InvalidHeader)?;
+
+    let heap_name =
+        |index: usize| match symbols.and_then(|s| s.symbol_name(index, SymbolType::Data)) {
+            Some(name) => format!("@{name}"),
+            None => format!("#{index}"),
+        };
+
+    let mut output = String::new();
+    writeln!(
+        output,
+        "; {partitions} heap partition(s), {data_size} byte(s) of data, {} byte(s) of code",
+        code.len()
+    )
+    .unwrap();
+
+    // read-only partitions come first, the assembler wants .rodata before .data
+    let (read_only, writable): (Vec<usize>, Vec<usize>) =
+        (0..partitions).partition(|index| heap.is_read_only(*index));
+    if !read_only.is_empty() {
+        output.push_str(".rodata\n");
+    }
+    for (position, index) in read_only.iter().chain(&writable).copied().enumerate() {
+        if position == read_only.len() {
+            output.push_str(".data\n");
+        }
+        let bytes = heap.get_slice(index).unwrap_or_default();
+        match std::str::from_utf8(bytes) {
+            Ok(text) if !text.chars().any(char::is_control) => {
+                writeln!(output, "{}: '{text}'", heap_name(index)).unwrap()
+            }
+            _ => {
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+                writeln!(output, "{}: {}", heap_name(index), hex.join(" ")).unwrap()
+            }
+        }
+    }
+
+    output.push_str(".code\n");
+    let mut cursor = Cursor::new(code);
+    while let Ok(byte) = cursor.read_u8() {
+        let offset = cursor.position() - 1;
+        let opcode = Opcode::from(byte);
+        if opcode == Opcode::IGL {
+            writeln!(output, "{offset:>4}: ; illegal opcode {byte}").unwrap();
+            continue;
+        }
+
+        let mut line = opcode.mnemonic();
+        for kind in opcode.signature() {
+            let text = match kind.decode(&mut cursor) {
+                Ok(Operand::Padding) => continue,
+                Ok(Operand::HeapIndex(index)) => heap_name(index as usize),
+                Ok(Operand::CodeOffset(value)) => {
+                    let next = cursor.position() as usize;
+                    let target = match opcode {
+                        Opcode::JMPFT => next.checked_add(value as usize),
+                        Opcode::JMPBT => next.checked_sub(value as usize),
+                        _ => Some(value as usize),
+                    };
+                    match target.and_then(|target| {
+                        symbols.and_then(|s| s.symbol_name(target, SymbolType::Label))
+                    }) {
+                        Some(name) => format!("@{name}"),
+                        None => format!("#{value}"),
+                    }
+                }
+                Ok(operand) => operand.to_string(),
+                Err(_) => {
+                    writeln!(output, "{offset:>4}: {line} ; missing operands").unwrap();
+                    return Ok(output);
+                }
+            };
+            line.push(' ');
+            line.push_str(&text);
+        }
+        writeln!(output, "{offset:>4}: {line}").unwrap();
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+
+    #[test]
+    fn test_disassemble() {
+        let mut asm = Assembler::new();
+        let program = asm
+            .assemble(
+                ".data\nhello: .str 'Hello'\nanswer: .int #42\n.code\nload $0 #500\nloadw $1 #-3\nloadf $2 #1.5\nprts @hello\nlt $0 $1 $0\nhlt",
+            )
+            .unwrap();
+
+        let output = disassemble(&program, Some(&asm.symbols)).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "; 2 heap partition(s), 9 byte(s) of data, 28 byte(s) of code",
+                ".data",
+                "@hello: 'Hello'",
+                "@answer: 2a 00 00 00",
+                ".code",
+                "   0: load $0 #500",
+                "   4: loadw $1 #-3",
+                "  10: loadf $2 #1.5",
+                "  20: prts @hello",
+                "  23: lt $0 $1",
+                "  27: hlt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_rodata() {
+        let mut asm = Assembler::new();
+        let program = asm
+            .assemble(".rodata\nhello: .str 'Hello'\n.data\nname: .str ''\n.code\nhlt")
+            .unwrap();
+
+        let output = disassemble(&program, Some(&asm.symbols)).unwrap();
+        assert!(output.contains(".rodata\n@hello: 'Hello'\n.data\n@name: ''\n.code\n"));
+    }
+
+    #[test]
+    fn test_disassemble_without_symbols() {
+        let mut heap = MemoryHeap::new(5);
+        heap.add("Hello".as_bytes().to_vec()).unwrap();
+        let program = crate::vm::VM::prepend_header(vec![18, 0, 0, 200, 1, 0], heap);
+
+        let output = disassemble(&program, None).unwrap();
+        assert!(output.contains("#0: 'Hello'\n"));
+        assert!(output.contains("   0: prts #0\n"));
+        assert!(output.contains("   3: ; illegal opcode 200\n"));
+        assert!(output.ends_with("   4: load $0 ; missing operands\n"));
+    }
+
+    #[test]
+    fn test_disassemble_jump_labels() {
+        let mut asm = Assembler::new();
+        let program = asm
+            .assemble(
+                ".data
+.code
+start: inc $0
+jmpf @end
+mid: jmpb @start
+end: jeq @mid
+hlt",
+            )
+            .unwrap();
+
+        let output = disassemble(&program, Some(&asm.symbols)).unwrap();
+        assert!(output.ends_with(
+            "   0: inc $0\n   2: jmpft @end\n   5: jmpbt @start\n   8: jeqt @mid\n  11: hlt\n"
+        ));
+
+        let output = disassemble(&program, None).unwrap();
+        assert!(output.contains("   2: jmpft #3\n   5: jmpbt #8\n   8: jeqt #5\n"));
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert_eq!(
+            disassemble(&[1, 2, 3], None),
+            Err(DisassemblerError::InvalidHeader)
+        );
+
+        // a table size that is not a whole number of entries points past the end
+        let mut program = PIE_HEADER_PREFIX.to_vec();
+        program.extend([4, 0, 0, 0]);
+        program.resize(PIE_HEADER_LENGTH, 0);
+        assert_eq!(
+            disassemble(&program, None),
+            Err(DisassemblerError::InvalidHeader)
+        );
+    }
+}
@@ -1,4 +1,11 @@
-#[derive(Debug, PartialEq, Clone, Copy)]
+use std::{
+    fmt,
+    io::{self, Cursor},
+};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Opcode {
     HLT,
     LOAD,
@@ -26,6 +33,59 @@ pub enum Opcode {
     GRPS,
     EQS,
     NEQS,
+    PUSH,
+    POP,
+    CALL,
+    RET,
+    AND,
+    OR,
+    XOR,
+    NOT,
+    SHL,
+    SHR,
+    SAR,
+    MOD,
+    REM,
+    INC,
+    DEC,
+    JNEQ,
+    LOADW,
+    LOADF,
+    ADDF,
+    SUBF,
+    MULF,
+    DIVF,
+    EQF,
+    NEQF,
+    GTF,
+    LTF,
+    GTQF,
+    LTQF,
+    ITOF,
+    FTOI,
+    PRTF,
+    // Jumps carrying their target as a code offset instead of reading a register
+    JMPT,
+    JMPFT,
+    JMPBT,
+    JEQT,
+    JNEQT,
+    CALLT,
+    LDI,
+    STI,
+    NEWP,
+    FREE,
+    // Heap instructions reading their partition ids from registers
+    PRTSR,
+    ASKSR,
+    GRPSR,
+    // Byte and word access at an offset inside a partition
+    LDB,
+    STB,
+    LDW,
+    STW,
+    // Loads a partition holding one f64 into a float register
+    LDF,
     IGL,
 }
 
@@ -58,6 +118,55 @@ impl From<u8> for Opcode {
             23 => Opcode::GRPS,
             24 => Opcode::EQS,
             25 => Opcode::NEQS,
+            26 => Opcode::PUSH,
+            27 => Opcode::POP,
+            28 => Opcode::CALL,
+            29 => Opcode::RET,
+            30 => Opcode::AND,
+            31 => Opcode::OR,
+            32 => Opcode::XOR,
+            33 => Opcode::NOT,
+            34 => Opcode::SHL,
+            35 => Opcode::SHR,
+            36 => Opcode::SAR,
+            37 => Opcode::MOD,
+            38 => Opcode::REM,
+            39 => Opcode::INC,
+            40 => Opcode::DEC,
+            41 => Opcode::JNEQ,
+            42 => Opcode::LOADW,
+            43 => Opcode::LOADF,
+            44 => Opcode::ADDF,
+            45 => Opcode::SUBF,
+            46 => Opcode::MULF,
+            47 => Opcode::DIVF,
+            48 => Opcode::EQF,
+            49 => Opcode::NEQF,
+            50 => Opcode::GTF,
+            51 => Opcode::LTF,
+            52 => Opcode::GTQF,
+            53 => Opcode::LTQF,
+            54 => Opcode::ITOF,
+            55 => Opcode::FTOI,
+            56 => Opcode::PRTF,
+            57 => Opcode::JMPT,
+            58 => Opcode::JMPFT,
+            59 => Opcode::JMPBT,
+            60 => Opcode::JEQT,
+            61 => Opcode::JNEQT,
+            62 => Opcode::CALLT,
+            63 => Opcode::LDI,
+            64 => Opcode::STI,
+            65 => Opcode::NEWP,
+            66 => Opcode::FREE,
+            67 => Opcode::PRTSR,
+            68 => Opcode::ASKSR,
+            69 => Opcode::GRPSR,
+            70 => Opcode::LDB,
+            71 => Opcode::STB,
+            72 => Opcode::LDW,
+            73 => Opcode::STW,
+            74 => Opcode::LDF,
             _ => Opcode::IGL,
         }
     }
@@ -67,6 +176,21 @@ impl From<String> for Opcode {
     fn from(v: String) -> Self {
         match v.as_str() {
             "load" => Opcode::LOAD,
+            "loadw" => Opcode::LOADW,
+            "loadf" => Opcode::LOADF,
+            "addf" => Opcode::ADDF,
+            "subf" => Opcode::SUBF,
+            "mulf" => Opcode::MULF,
+            "divf" => Opcode::DIVF,
+            "eqf" => Opcode::EQF,
+            "neqf" => Opcode::NEQF,
+            "gtf" => Opcode::GTF,
+            "ltf" => Opcode::LTF,
+            "gtqf" => Opcode::GTQF,
+            "ltqf" => Opcode::LTQF,
+            "itof" => Opcode::ITOF,
+            "ftoi" => Opcode::FTOI,
+            "prtf" => Opcode::PRTF,
             "add" => Opcode::ADD,
             "sub" => Opcode::SUB,
             "mul" => Opcode::MUL,
@@ -81,7 +205,8 @@ impl From<String> for Opcode {
             "gt" => Opcode::GT,
             "ltq" => Opcode::LTQ,
             "lt" => Opcode::LT,
-            "jeq" => Opcode::JEQ,
+            "jeq" | "jmpe" => Opcode::JEQ,
+            "jneq" | "jmpne" => Opcode::JNEQ,
             "aloc" => Opcode::ALOC,
             "prts" => Opcode::PRTS,
             "prti" => Opcode::PRTI,
@@ -92,11 +217,272 @@ impl From<String> for Opcode {
             "grps" => Opcode::GRPS,
             "eqs" => Opcode::EQS,
             "neqs" => Opcode::NEQS,
+            "push" => Opcode::PUSH,
+            "pop" => Opcode::POP,
+            "call" => Opcode::CALL,
+            "ret" => Opcode::RET,
+            "and" => Opcode::AND,
+            "or" => Opcode::OR,
+            "xor" => Opcode::XOR,
+            "not" => Opcode::NOT,
+            "shl" => Opcode::SHL,
+            "shr" => Opcode::SHR,
+            "sar" => Opcode::SAR,
+            "mod" => Opcode::MOD,
+            "rem" => Opcode::REM,
+            "inc" => Opcode::INC,
+            "dec" => Opcode::DEC,
+            "jmpt" => Opcode::JMPT,
+            "jmpft" => Opcode::JMPFT,
+            "jmpbt" => Opcode::JMPBT,
+            "jeqt" => Opcode::JEQT,
+            "jneqt" => Opcode::JNEQT,
+            "callt" => Opcode::CALLT,
+            "ldi" => Opcode::LDI,
+            "sti" => Opcode::STI,
+            "newp" => Opcode::NEWP,
+            "free" => Opcode::FREE,
+            "prtsr" => Opcode::PRTSR,
+            "asksr" => Opcode::ASKSR,
+            "grpsr" => Opcode::GRPSR,
+            "ldb" => Opcode::LDB,
+            "stb" => Opcode::STB,
+            "ldw" => Opcode::LDW,
+            "stw" => Opcode::STW,
+            "ldf" => Opcode::LDF,
             _ => Opcode::IGL,
         }
     }
 }
 
+// What an opcode reads after its own byte. Registers and padding take one byte,
+// shorts, heap indices and code offsets two big endian bytes, wide integers four
+// and floats eight.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OperandKind {
+    Register,
+    Short,
+    Wide,
+    Float,
+    HeapIndex,
+    // A jump target, relative to the code start or for JMPFT and JMPBT to the next instruction
+    CodeOffset,
+    // Unused byte, the assembler accepts a register there or fills it with 0
+    Padding,
+}
+
+impl OperandKind {
+    pub fn size(self) -> usize {
+        match self {
+            OperandKind::Register | OperandKind::Padding => 1,
+            OperandKind::Short | OperandKind::HeapIndex | OperandKind::CodeOffset => 2,
+            OperandKind::Wide => 4,
+            OperandKind::Float => 8,
+        }
+    }
+
+    pub fn decode(self, bytes: &mut Cursor<&[u8]>) -> io::Result<Operand> {
+        Ok(match self {
+            OperandKind::Register => Operand::Register(bytes.read_u8()?),
+            OperandKind::Short => Operand::Short(bytes.read_u16::<BigEndian>()?),
+            OperandKind::Wide => Operand::Wide(bytes.read_i32::<BigEndian>()?),
+            OperandKind::Float => Operand::Float(bytes.read_f64::<BigEndian>()?),
+            OperandKind::HeapIndex => Operand::HeapIndex(bytes.read_u16::<BigEndian>()?),
+            OperandKind::CodeOffset => Operand::CodeOffset(bytes.read_u16::<BigEndian>()?),
+            OperandKind::Padding => {
+                bytes.read_u8()?;
+                Operand::Padding
+            }
+        })
+    }
+}
+
+// A decoded operand, displayed the way it is written in assembly
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operand {
+    Register(u8),
+    Short(u16),
+    Wide(i32),
+    Float(f64),
+    HeapIndex(u16),
+    CodeOffset(u16),
+    Padding,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "${register}"),
+            Operand::Short(value) => write!(f, "#{value}"),
+            Operand::Wide(value) => write!(f, "#{value}"),
+            Operand::Float(value) => write!(f, "#{value:?}"),
+            Operand::HeapIndex(index) => write!(f, "#{index}"),
+            Operand::CodeOffset(offset) => write!(f, "#{offset}"),
+            Operand::Padding => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for OperandKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OperandKind::Register | OperandKind::Padding => "a register",
+            OperandKind::Short => "a 16-bit integer",
+            OperandKind::Wide => "a 32-bit integer",
+            OperandKind::Float => "a float",
+            OperandKind::HeapIndex => "a heap index",
+            OperandKind::CodeOffset => "a code label",
+        })
+    }
+}
+
+impl Opcode {
+    // The single definition of every opcode's operands, used by the VM to check
+    // instructions are complete, by the assembler to validate them and by the disassembler.
+    pub fn signature(self) -> &'static [OperandKind] {
+        use OperandKind::*;
+
+        match self {
+            Opcode::HLT | Opcode::RET | Opcode::IGL => &[],
+            Opcode::LOAD => &[Register, Short],
+            Opcode::LOADW => &[Register, Wide],
+            Opcode::LOADF => &[Register, Float],
+            Opcode::ADD
+            | Opcode::SUB
+            | Opcode::MUL
+            | Opcode::DIV
+            | Opcode::MOD
+            | Opcode::AND
+            | Opcode::OR
+            | Opcode::XOR
+            | Opcode::SHL
+            | Opcode::SHR
+            | Opcode::SAR
+            | Opcode::ADDF
+            | Opcode::SUBF
+            | Opcode::MULF
+            | Opcode::DIVF
+            | Opcode::GRPSR
+            | Opcode::LDB
+            | Opcode::STB
+            | Opcode::LDW
+            | Opcode::STW => &[Register, Register, Register],
+            Opcode::NOT | Opcode::ITOF | Opcode::FTOI | Opcode::NEWP | Opcode::ASKSR => {
+                &[Register, Register]
+            }
+            Opcode::EQ
+            | Opcode::NEQ
+            | Opcode::GT
+            | Opcode::LT
+            | Opcode::GTQ
+            | Opcode::LTQ
+            | Opcode::EQF
+            | Opcode::NEQF
+            | Opcode::GTF
+            | Opcode::LTF
+            | Opcode::GTQF
+            | Opcode::LTQF => &[Register, Register, Padding],
+            Opcode::REM
+            | Opcode::INC
+            | Opcode::DEC
+            | Opcode::JMP
+            | Opcode::JMPF
+            | Opcode::JMPB
+            | Opcode::JEQ
+            | Opcode::JNEQ
+            | Opcode::CALL
+            | Opcode::ALOC
+            | Opcode::PRTI
+            | Opcode::PRTF
+            | Opcode::SLP
+            | Opcode::SLPS
+            | Opcode::PUSH
+            | Opcode::POP
+            | Opcode::FREE
+            | Opcode::PRTSR => &[Register],
+            Opcode::PRTS => &[HeapIndex],
+            Opcode::ASKI | Opcode::LDI | Opcode::LDF => &[HeapIndex, Register],
+            Opcode::STI => &[Register, HeapIndex],
+            Opcode::ASKS | Opcode::EQS | Opcode::NEQS => &[HeapIndex, HeapIndex],
+            Opcode::GRPS => &[HeapIndex, HeapIndex, HeapIndex],
+            Opcode::JMPT
+            | Opcode::JMPFT
+            | Opcode::JMPBT
+            | Opcode::JEQT
+            | Opcode::JNEQT
+            | Opcode::CALLT => &[CodeOffset],
+        }
+    }
+
+    // Number of bytes following the opcode
+    pub fn operands_size(self) -> usize {
+        self.signature().iter().map(|kind| kind.size()).sum()
+    }
+
+    // Decodes the operands following the opcode, padding left out
+    pub fn decode_operands(self, bytes: &mut Cursor<&[u8]>) -> io::Result<Vec<Operand>> {
+        let mut operands = Vec::new();
+        for kind in self.signature() {
+            match kind.decode(bytes)? {
+                Operand::Padding => {}
+                operand => operands.push(operand),
+            }
+        }
+        Ok(operands)
+    }
+
+    pub fn mnemonic(self) -> String {
+        format!("{self:?}").to_lowercase()
+    }
+
+    // Position of the heap index operand, or handle register, the instruction writes to
+    pub fn written_heap_operand(self) -> Option<usize> {
+        match self {
+            Opcode::FREE => Some(0),
+            Opcode::ASKS | Opcode::ASKSR | Opcode::STI | Opcode::STB | Opcode::STW => Some(1),
+            Opcode::GRPS | Opcode::GRPSR => Some(2),
+            _ => None,
+        }
+    }
+
+    // Whether the heap operands are registers holding partition ids
+    pub fn reads_handles(self) -> bool {
+        !self.handle_operands().is_empty()
+    }
+
+    // Positions of the register operands holding partition ids
+    pub fn handle_operands(self) -> &'static [usize] {
+        match self {
+            Opcode::FREE | Opcode::PRTSR | Opcode::LDB | Opcode::LDW => &[0],
+            Opcode::STB | Opcode::STW => &[1],
+            Opcode::ASKSR => &[0, 1],
+            Opcode::GRPSR => &[0, 1, 2],
+            _ => &[],
+        }
+    }
+
+    // Position of the register the instruction stores a new partition id in
+    pub fn created_handle_operand(self) -> Option<usize> {
+        match self {
+            Opcode::NEWP => Some(1),
+            _ => None,
+        }
+    }
+
+    // The form of a register jump that carries its target instead
+    pub fn with_target(self) -> Option<Opcode> {
+        match self {
+            Opcode::JMP => Some(Opcode::JMPT),
+            Opcode::JMPF => Some(Opcode::JMPFT),
+            Opcode::JMPB => Some(Opcode::JMPBT),
+            Opcode::JEQ => Some(Opcode::JEQT),
+            Opcode::JNEQ => Some(Opcode::JNEQT),
+            Opcode::CALL => Some(Opcode::CALLT),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Instruction {
     opcode: Opcode,
@@ -104,7 +490,7 @@ pub struct Instruction {
 
 impl Instruction {
     pub fn new(opcode: Opcode) -> Self {
-        Self { opcode: opcode }
+        Self { opcode }
     }
 }
 
@@ -128,7 +514,40 @@ mod tests {
     fn test_str_to_opcode() {
         let opcode = Opcode::from("load".to_owned());
         assert_eq!(opcode, Opcode::LOAD);
+        let opcode = Opcode::from("sar".to_owned());
+        assert_eq!(opcode, Opcode::SAR);
+        let opcode = Opcode::from("jmpe".to_owned());
+        assert_eq!(opcode, Opcode::JEQ);
+        let opcode = Opcode::from("jmpne".to_owned());
+        assert_eq!(opcode, Opcode::JNEQ);
         let opcode = Opcode::from("illegal".to_owned());
         assert_eq!(opcode, Opcode::IGL);
     }
+
+    #[test]
+    fn test_signatures() {
+        for byte in 0..=u8::MAX {
+            let opcode = Opcode::from(byte);
+            if opcode != Opcode::IGL {
+                assert_eq!(opcode as u8, byte);
+                assert_eq!(Opcode::from(opcode.mnemonic()), opcode);
+            }
+        }
+        assert_eq!(Opcode::LOAD.operands_size(), 3);
+        assert_eq!(Opcode::LOADF.operands_size(), 9);
+        assert_eq!(Opcode::EQ.operands_size(), 3);
+        assert_eq!(Opcode::GRPS.operands_size(), 6);
+        assert_eq!(Opcode::JMPFT.operands_size(), 2);
+        assert_eq!(Opcode::JEQ.with_target(), Some(Opcode::JEQT));
+        assert_eq!(Opcode::JEQT.with_target(), None);
+        assert_eq!(Opcode::GRPSR.operands_size(), 3);
+        assert!(Opcode::ASKSR.reads_handles());
+        assert!(!Opcode::ASKS.reads_handles());
+        assert_eq!(Opcode::STW.handle_operands(), &[1]);
+        assert_eq!(Opcode::NEWP.created_handle_operand(), Some(1));
+        assert_eq!(
+            Opcode::GRPS.signature()[Opcode::GRPS.written_heap_operand().unwrap()],
+            OperandKind::HeapIndex
+        );
+    }
 }
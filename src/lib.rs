@@ -1,10 +1,12 @@
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
+
 use std::{fs::File, io::Read, path::Path};
 
-use assembler::Assembler;
-use cli::{REPLArgs, RunFileArgs};
+use assembler::{Assembler, PIE_HEADER_PREFIX};
+use cli::{DisassembleArgs, REPLArgs, RunFileArgs};
 use repl::REPL;
 use rustyline::error::ReadlineError;
-use vm::VM;
+use vm::{events::VMEventType, profiler::Profile, snapshot::Snapshot, trace::Tracer, VM};
 
 extern crate anyhow;
 extern crate chrono;
@@ -22,6 +24,7 @@ extern crate uuid;
 
 pub mod assembler;
 pub mod cli;
+pub mod disassembler;
 pub mod instruction;
 pub mod repl;
 pub mod scheduler;
@@ -30,6 +33,11 @@ pub mod vm;
 
 pub fn start_repl(args: REPLArgs) -> Result<(), ReadlineError> {
     let mut repl = REPL::new(args.mode)?;
+    repl.set_budget(args.budget);
+    repl.set_heap_limit(args.heap_limit);
+    repl.set_arithmetic_mode(args.arithmetic_mode);
+    repl.set_clock(args.clock);
+    repl.set_history_cap(args.history_cap);
     repl.run();
     Ok(())
 }
@@ -40,9 +48,7 @@ fn read_file(tmp: &str) -> String {
         Ok(mut fh) => {
             let mut contents = String::new();
             match fh.read_to_string(&mut contents) {
-                Ok(_) => {
-                    return contents;
-                }
+                Ok(_) => contents,
                 Err(e) => {
                     println!("There was an error reading file: {:?}", e);
                     std::process::exit(1);
@@ -57,44 +63,123 @@ fn read_file(tmp: &str) -> String {
 }
 
 pub fn run_file(args: RunFileArgs) {
-    let program = read_file(args.filename);
-    let mut asm = Assembler::new();
     let mut vm = VM::new();
     vm.logical_cores = args.num_threads;
-    let program = asm.assemble(&program);
-    match program {
-        Ok(p) => {
-            vm.add_bytes(p);
+    vm.budget = args.budget;
+    vm.memory_heap.set_limit(args.heap_limit);
+    vm.arithmetic_mode = args.arithmetic_mode;
+    vm.clock = args.clock;
+    vm.tracer = args
+        .trace
+        .map(|format| Tracer::new(format, std::io::stderr()));
+    if args.profile || args.collapsed_stacks.is_some() {
+        vm.profile = Some(Profile::default());
+    }
 
-            let events = vm.run();
-            if args.debug {
-                println!("--------------------------");
-                println!("VM Events");
-                println!("--------------------------");
-                for event in &events {
-                    println!("{:#?}", event);
-                }
-                println!("--------------------------");
-                println!("Non-null Registers");
-                println!("--------------------------");
-                for (register, value) in vm.registers.iter().enumerate() {
-                    if *value != 0 {
-                        println!("${register} = {value}");
-                    }
-                }
-                println!("--------------------------");
-                println!("Memory Heap as UTF-8 Strings");
-                println!("--------------------------");
-                for bytes in vm.memory_heap.into_iter() {
-                    println!("{}", std::str::from_utf8(&bytes).unwrap());
+    // stays empty when resuming, the profile then has no labels
+    let mut asm = Assembler::new();
+    let events = if args.resume {
+        let restored =
+            Snapshot::load(Path::new(args.filename)).and_then(|snapshot| vm.restore(snapshot));
+        if let Err(e) = restored {
+            println!("Unable to resume {}: {e}", args.filename);
+            std::process::exit(1);
+        }
+        vm.resume()
+    } else {
+        let program = read_file(args.filename);
+        match asm.assemble(&program) {
+            Ok(p) => {
+                vm.add_bytes(p);
+                vm.run()
+            }
+            Err(errors) => {
+                println!("Encountered {} assembler error(s):", errors.len());
+                for error in errors {
+                    println!("{error}");
                 }
+                return;
+            }
+        }
+    };
+
+    match events.last().map(|e| e.event()) {
+        Some(VMEventType::Crash { error, .. }) => println!("The VM crashed: {error}"),
+        Some(VMEventType::BudgetExhausted { executed, pc }) => {
+            println!("The VM ran out of budget after {executed} instructions, at {pc}")
+        }
+        _ => {}
+    }
+    if let Some(profile) = &vm.profile {
+        print!("{}", profile.report(Some(&asm.symbols)));
+        if let Some(path) = args.collapsed_stacks {
+            if let Err(e) = std::fs::write(path, profile.collapsed(Some(&asm.symbols))) {
+                println!("Unable to write the collapsed stacks to {path}: {e}");
+            }
+        }
+    }
+    if args.debug {
+        println!("--------------------------");
+        println!("VM Events");
+        println!("--------------------------");
+        for event in &events {
+            println!("{:#?}", event);
+        }
+        println!("--------------------------");
+        println!("Non-null Registers");
+        println!("--------------------------");
+        for (register, value) in vm.registers.iter().enumerate() {
+            if *value != 0 {
+                println!("${register} = {value}");
+            }
+        }
+        for (register, value) in vm.float_registers.iter().enumerate() {
+            if *value != 0.0 {
+                println!("$f{register} = {value}");
             }
         }
-        Err(errors) => {
-            println!("Encountered {} assembler error(s):", errors.len());
-            for error in errors {
-                println!("{error}");
+        println!("Remainder = {}", vm.remainder());
+        println!("Overflow = {}", vm.overflow_flag());
+        if let Some(virtual_time) = vm.virtual_time() {
+            println!("Virtual time = {virtual_time:?}");
+        }
+        println!("--------------------------");
+        println!("Memory Heap as UTF-8 Strings");
+        println!("--------------------------");
+        for bytes in vm.memory_heap.into_iter() {
+            println!("{}", String::from_utf8_lossy(&bytes));
+        }
+    }
+}
+
+// Accepts both assembly sources and already assembled bytecode
+pub fn disassemble_file(args: DisassembleArgs) {
+    let bytes = match std::fs::read(args.filename) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            println!("File not found: {:?}", e);
+            std::process::exit(1)
+        }
+    };
+
+    let disassembled = if bytes.starts_with(&PIE_HEADER_PREFIX) {
+        disassembler::disassemble(&bytes, None)
+    } else {
+        let mut asm = Assembler::new();
+        match asm.assemble(&String::from_utf8_lossy(&bytes)) {
+            Ok(program) => disassembler::disassemble(&program, Some(&asm.symbols)),
+            Err(errors) => {
+                println!("Encountered {} assembler error(s):", errors.len());
+                for error in errors {
+                    println!("{error}");
+                }
+                return;
             }
         }
+    };
+
+    match disassembled {
+        Ok(assembly) => print!("{assembly}"),
+        Err(error) => println!("Unable to disassemble {}: {error}", args.filename),
     }
 }
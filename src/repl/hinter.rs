@@ -17,6 +17,7 @@ pub struct CommandHint {
 }
 
 impl Hint for CommandHint {
+    #[allow(clippy::misnamed_getters)]
     fn display(&self) -> &str {
         &self.display_color
     }
@@ -78,10 +79,30 @@ pub fn rk_hints() -> HashSet<CommandHint> {
     set.insert(CommandHint::new("!clear_registers", "!clear_registers"));
     set.insert(CommandHint::new("!registers", "!registers"));
     set.insert(CommandHint::new("!symbols", "!symbols"));
+    set.insert(CommandHint::new("!disassemble", "!disassemble"));
     set.insert(CommandHint::new(
         "!load_file path/to/file.rk",
         "!load_file ",
     ));
-    set.insert(CommandHint::new("!spawn path/to/file.rk", "!spawn "));
+    set.insert(CommandHint::new(
+        "!spawn path/to/file.rk [max heap bytes]",
+        "!spawn ",
+    ));
+    set.insert(CommandHint::new("!break offset_or_label", "!break "));
+    set.insert(CommandHint::new("!delete offset_or_label", "!delete "));
+    set.insert(CommandHint::new("!step [count]", "!step"));
+    set.insert(CommandHint::new("!continue", "!continue"));
+    set.insert(CommandHint::new("!trace human|json|off", "!trace"));
+    set.insert(CommandHint::new("!back [count]", "!back"));
+    set.insert(CommandHint::new("!clock", "!clock"));
+    set.insert(CommandHint::new("!rewind [instruction count]", "!rewind"));
+    set.insert(CommandHint::new(
+        "!save_state path/to/snapshot.json",
+        "!save_state ",
+    ));
+    set.insert(CommandHint::new(
+        "!load_state path/to/snapshot.json",
+        "!load_state ",
+    ));
     set
 }
@@ -1,7 +1,19 @@
 use crate::{
     assembler::{program_parser::program, symbols::SymbolTable, Assembler},
+    disassembler::disassemble,
     scheduler::Scheduler,
-    vm::VM,
+    vm::{
+        arithmetic::ArithmeticMode,
+        budget::Budget,
+        clock::Clock,
+        debugger::StepResult,
+        history::DEFAULT_HISTORY_CAP,
+        io::VMIo,
+        memory::HeapLimit,
+        snapshot::Snapshot,
+        trace::{TraceFormat, Tracer},
+        VM,
+    },
 };
 use rustyline::{error::ReadlineError, Editor};
 use std::{fs::File, io::Read, num::ParseIntError, path::Path};
@@ -27,20 +39,52 @@ pub struct REPL {
     scheduler: Scheduler,
     rl: Editor<RkHinter>,
     helper: RkHinter,
+    // Labels to break at, resolved again every time a file is loaded
+    label_breakpoints: Vec<String>,
 }
 
 impl REPL {
     pub fn new(mode: REPLMode) -> Result<Self, ReadlineError> {
         Ok(Self {
             mode,
-            vm: VM::new(),
+            vm: {
+                let mut vm = VM::new();
+                vm.set_history_cap(DEFAULT_HISTORY_CAP);
+                vm
+            },
             asm: Assembler::new(),
             scheduler: Scheduler::new(),
             rl: Editor::new()?,
             helper: RkHinter { hints: rk_hints() },
+            label_breakpoints: Vec::new(),
         })
     }
 
+    // Applies to every program loaded or spawned from now on
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.vm.budget = budget;
+    }
+
+    pub fn set_heap_limit(&mut self, limit: HeapLimit) {
+        self.vm.memory_heap.set_limit(limit);
+    }
+
+    pub fn set_arithmetic_mode(&mut self, mode: ArithmeticMode) {
+        self.vm.arithmetic_mode = mode;
+    }
+
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.vm.clock = clock;
+    }
+
+    pub fn set_history_cap(&mut self, cap: usize) {
+        self.vm.set_history_cap(cap);
+    }
+
+    pub fn set_io(&mut self, io: Box<dyn VMIo>) {
+        self.vm.io = io;
+    }
+
     pub fn run(&mut self) {
         println!("Welcome to Rocky! Let's be nerds!");
 
@@ -81,7 +125,13 @@ impl REPL {
             return;
         }
         let (_, result) = parsed_program.unwrap();
-        let bytecode = result.to_bytes(&SymbolTable::new());
+        let bytecode = match result.to_bytes(&SymbolTable::new()) {
+            Ok(bytecode) => bytecode,
+            Err(error) => {
+                println!("Unable to assemble input: {error}");
+                return;
+            }
+        };
         for byte in bytecode {
             self.vm.add_byte(byte);
         }
@@ -112,8 +162,19 @@ impl REPL {
             "!clear_registers" => self.clear_registers(&args[1..]),
             "!registers" => self.registers(&args[1..]),
             "!symbols" => self.symbols(&args[1..]),
+            "!disassemble" => self.disassemble(&args[1..]),
             "!load_file" => self.load_file(&args[1..]),
             "!spawn" => self.spawn(&args[1..]),
+            "!break" => self.add_breakpoint(&args[1..]),
+            "!delete" => self.delete_breakpoint(&args[1..]),
+            "!step" => self.step(&args[1..]),
+            "!continue" => self.continue_execution(&args[1..]),
+            "!back" => self.step_back(&args[1..]),
+            "!rewind" => self.rewind(&args[1..]),
+            "!clock" => self.clock(&args[1..]),
+            "!trace" => self.trace(&args[1..]),
+            "!save_state" => self.save_state(&args[1..]),
+            "!load_state" => self.load_state(&args[1..]),
             _ => {
                 println!("Invalid command")
             }
@@ -137,6 +198,9 @@ impl REPL {
     fn registers(&self, _args: &[&str]) {
         println!("Listing registers and all contents:");
         println!("{:#?}", self.vm.registers);
+        println!("Float registers: {:?}", self.vm.float_registers);
+        println!("Remainder: {}", self.vm.remainder());
+        println!("Overflow: {}", self.vm.overflow_flag());
         println!("End of Register Listing")
     }
 
@@ -148,6 +212,13 @@ impl REPL {
         println!("End of Symbols Listing")
     }
 
+    fn disassemble(&self, _args: &[&str]) {
+        match disassemble(&self.vm.program, Some(&self.asm.symbols)) {
+            Ok(assembly) => print!("{assembly}"),
+            Err(error) => println!("Unable to disassemble the program: {error}"),
+        }
+    }
+
     fn clear_program(&mut self, _args: &[&str]) {
         self.vm.program.clear();
         self.vm.program_cursor.set_position(0);
@@ -158,7 +229,7 @@ impl REPL {
     }
 
     fn load_file(&mut self, args: &[&str]) {
-        if args.len() == 0 {
+        if args.is_empty() {
             println!("Usage: !load_file path/to/file.rk");
             return;
         }
@@ -167,10 +238,15 @@ impl REPL {
         let contents = self.get_data_from_load(filename);
         if let Some(contents) = contents {
             match self.asm.assemble(&contents) {
-                Ok(mut assembled_program) => {
+                Ok(assembled_program) => {
                     println!("Sending assembled program to VM");
-                    self.vm.program.append(&mut assembled_program);
-                    self.vm.run();
+                    self.vm.program = assembled_program;
+                    self.vm.update_program_cursor();
+                    self.resolve_label_breakpoints();
+                    if self.vm.start().is_ok() {
+                        let result = self.vm.continue_execution();
+                        self.report(result);
+                    }
                 }
                 Err(errors) => {
                     for error in errors {
@@ -181,12 +257,231 @@ impl REPL {
         }
     }
 
+    fn add_breakpoint(&mut self, args: &[&str]) {
+        if args.is_empty() {
+            println!("Listing breakpoints:");
+            for offset in self.vm.breakpoints() {
+                println!("{offset}");
+            }
+            for label in &self.label_breakpoints {
+                println!("@{label}");
+            }
+            println!("End of Breakpoints Listing");
+            return;
+        }
+
+        match Self::parse_breakpoint(args[0]) {
+            Ok(offset) => {
+                self.vm.add_breakpoint(offset);
+                println!("Breakpoint set at offset {offset}");
+            }
+            Err(label) => {
+                if !self.label_breakpoints.contains(&label) {
+                    self.label_breakpoints.push(label.clone());
+                }
+                match self.vm.add_label_breakpoint(&label, &self.asm.symbols) {
+                    Some(offset) => println!("Breakpoint set at @{label} (offset {offset})"),
+                    None => println!("Breakpoint set at @{label} once a file defines it"),
+                }
+            }
+        }
+    }
+
+    fn delete_breakpoint(&mut self, args: &[&str]) {
+        if args.is_empty() {
+            self.vm.clear_breakpoints();
+            self.label_breakpoints.clear();
+            println!("Deleted all breakpoints");
+            return;
+        }
+
+        let offset = match Self::parse_breakpoint(args[0]) {
+            Ok(offset) => Some(offset),
+            Err(label) => {
+                self.label_breakpoints.retain(|l| *l != label);
+                self.asm.symbols.symbol_value(&label)
+            }
+        };
+        match offset {
+            Some(offset) if self.vm.remove_breakpoint(offset) => {
+                println!("Deleted breakpoint at offset {offset}")
+            }
+            _ => println!("No breakpoint at {}", args[0]),
+        }
+    }
+
+    fn step(&mut self, args: &[&str]) {
+        let count = match args.first().map(|count| count.parse::<usize>()) {
+            Some(Ok(count)) => count,
+            Some(Err(_)) => {
+                println!("Usage: !step [count]");
+                return;
+            }
+            None => 1,
+        };
+
+        for _ in 0..count {
+            let result = self.vm.run_once();
+            if result != StepResult::Continued {
+                self.report(result);
+                return;
+            }
+        }
+        self.report(StepResult::Continued);
+    }
+
+    fn continue_execution(&mut self, _args: &[&str]) {
+        let result = self.vm.continue_execution();
+        self.report(result);
+    }
+
+    fn step_back(&mut self, args: &[&str]) {
+        let count = match args.first().map(|count| count.parse::<usize>()) {
+            Some(Ok(count)) => count,
+            Some(Err(_)) => {
+                println!("Usage: !back [count]");
+                return;
+            }
+            None => 1,
+        };
+
+        let undone = (0..count).take_while(|_| self.vm.step_back()).count();
+        if undone < count {
+            println!("Only {undone} instruction(s) were recorded");
+        }
+        self.report_position();
+    }
+
+    fn rewind(&mut self, args: &[&str]) {
+        let executed = match args.first().map(|executed| executed.parse::<u64>()) {
+            Some(Ok(executed)) => executed,
+            Some(Err(_)) => {
+                println!("Usage: !rewind [instruction count]");
+                return;
+            }
+            None => 0,
+        };
+
+        self.vm.rewind(executed);
+        if self.vm.executed() > executed {
+            println!("The history does not go further back");
+        }
+        self.report_position();
+    }
+
+    fn report_position(&self) {
+        match self.vm.code_offset() {
+            Some(offset) => println!(
+                "Back at offset {offset}, after {} instruction(s)",
+                self.vm.executed()
+            ),
+            None => println!("Back after {} instruction(s)", self.vm.executed()),
+        }
+    }
+
+    fn clock(&self, _args: &[&str]) {
+        println!("Clock: {}", self.vm.clock);
+    }
+
+    fn save_state(&self, args: &[&str]) {
+        if args.is_empty() {
+            println!("Usage: !save_state path/to/snapshot.json");
+            return;
+        }
+
+        match self.vm.snapshot().save(Path::new(args[0])) {
+            Ok(()) => println!("Saved the VM state to {}", args[0]),
+            Err(e) => println!("Unable to save the VM state: {e}"),
+        }
+    }
+
+    fn load_state(&mut self, args: &[&str]) {
+        if args.is_empty() {
+            println!("Usage: !load_state path/to/snapshot.json");
+            return;
+        }
+
+        match Snapshot::load(Path::new(args[0])).and_then(|snapshot| self.vm.restore(snapshot)) {
+            Ok(()) => match self.vm.code_offset() {
+                Some(offset) => println!("Loaded the VM state, paused at offset {offset}"),
+                None => println!("Loaded the VM state"),
+            },
+            Err(e) => println!("Unable to load the VM state: {e}"),
+        }
+    }
+
+    fn trace(&mut self, args: &[&str]) {
+        let format = match args.first() {
+            Some(&"off") => None,
+            Some(format) => match format.parse::<TraceFormat>() {
+                Ok(format) => Some(format),
+                Err(error) => {
+                    println!("{error}, use human, json or off");
+                    return;
+                }
+            },
+            // toggles between off and human readable
+            None if self.vm.tracer.is_some() => None,
+            None => Some(TraceFormat::Human),
+        };
+
+        self.vm.tracer = format.map(|format| Tracer::new(format, std::io::stdout()));
+        match format {
+            Some(format) => println!("Tracing instructions ({format:?})"),
+            None => println!("Tracing is off"),
+        }
+    }
+
+    // Offsets are plain numbers, anything else is a label with an optional leading @
+    fn parse_breakpoint(arg: &str) -> Result<usize, String> {
+        arg.parse::<usize>()
+            .map_err(|_| arg.trim_start_matches('@').to_owned())
+    }
+
+    fn resolve_label_breakpoints(&mut self) {
+        for label in &self.label_breakpoints {
+            if self
+                .vm
+                .add_label_breakpoint(label, &self.asm.symbols)
+                .is_none()
+            {
+                println!("Label @{label} is not defined, its breakpoint is ignored");
+            }
+        }
+    }
+
+    fn report(&self, result: StepResult) {
+        match result {
+            StepResult::Continued => match self.vm.code_offset() {
+                Some(offset) => println!("Paused at offset {offset}"),
+                None => println!("Paused"),
+            },
+            StepResult::Halted(code) => println!("Program halted with code {code}"),
+            StepResult::Faulted(error) => println!("The VM crashed: {error}"),
+            StepResult::Breakpoint(offset) => println!("Hit breakpoint at offset {offset}"),
+            StepResult::BudgetExhausted { executed } => {
+                println!("The VM ran out of budget after {executed} instructions")
+            }
+        }
+    }
+
     fn spawn(&mut self, args: &[&str]) {
-        if args.len() == 0 {
-            println!("Usage: !spawn path/to/file.rk");
+        if args.is_empty() {
+            println!("Usage: !spawn path/to/file.rk [max heap bytes]");
             return;
         }
 
+        let mut heap_limit = self.vm.memory_heap.limit();
+        if let Some(max_size) = args.get(1) {
+            match max_size.parse::<usize>() {
+                Ok(max_size) => heap_limit.max_size = Some(max_size),
+                Err(_) => {
+                    println!("Invalid maximum heap size: {max_size}");
+                    return;
+                }
+            }
+        }
+
         let filename = Path::new(args[0]);
         let contents = self.get_data_from_load(filename);
         if let Some(contents) = contents {
@@ -194,7 +489,7 @@ impl REPL {
                 Ok(mut assembled_program) => {
                     println!("Sending assembled program to VM");
                     self.vm.program.append(&mut assembled_program);
-                    self.scheduler.get_thread(self.vm.clone());
+                    self.scheduler.get_thread(self.vm.clone(), heap_limit);
                 }
                 Err(errors) => {
                     for error in errors {
@@ -209,7 +504,7 @@ impl REPL {
         let split = i.split(" ").collect::<Vec<&str>>();
         let mut results: Vec<u8> = vec![];
         for hex_string in split {
-            let byte = u8::from_str_radix(&hex_string, 16);
+            let byte = u8::from_str_radix(hex_string, 16);
             match byte {
                 Ok(result) => {
                     results.push(result);
@@ -223,7 +518,7 @@ impl REPL {
     }
 
     fn get_data_from_load(&mut self, filename: &Path) -> Option<String> {
-        let mut f = match File::open(&filename) {
+        let mut f = match File::open(filename) {
             Ok(f) => f,
             Err(e) => {
                 println!("There was an error opening that file: {:?}", e);
@@ -1,4 +1,4 @@
-use crate::vm::{events::VMEvent, VM};
+use crate::vm::{events::VMEvent, memory::HeapLimit, VM};
 use std::{
     io::{self, Write},
     thread,
@@ -19,8 +19,14 @@ impl Scheduler {
         }
     }
 
-    pub fn get_thread(&self, mut vm: VM) -> thread::JoinHandle<Vec<VMEvent>> {
+    // Every process gets its own heap limit
+    pub fn get_thread(
+        &self,
+        mut vm: VM,
+        heap_limit: HeapLimit,
+    ) -> thread::JoinHandle<Vec<VMEvent>> {
         thread::spawn(move || {
+            vm.memory_heap.set_limit(heap_limit);
             let events = vm.run();
             println!("--------------------------");
             println!("VM Events");
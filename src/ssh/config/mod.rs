@@ -15,7 +15,7 @@ pub struct SSHConfig {
 
 impl SSHConfig {
     pub fn load(filename: &str) -> Self {
-        let mut f = match File::open(&filename) {
+        let mut f = match File::open(filename) {
             Ok(f) => f,
             Err(_) => return Self::default(),
         };
@@ -36,7 +36,7 @@ impl SSHConfig {
     }
 
     pub fn save(&self, filename: &str) {
-        let mut f = match File::create(&filename) {
+        let mut f = match File::create(filename) {
             Ok(f) => f,
             Err(e) => {
                 println!("There was an error creating that file: {e:?}");
@@ -74,18 +74,18 @@ impl Clone for SSHConfig {
                 .client_keys
                 .iter()
                 .map(|k| match k {
-                    PublicKey::Ed25519(public) => PublicKey::Ed25519(ed25519::PublicKey {
-                        key: public.key.clone(),
-                    }),
+                    PublicKey::Ed25519(public) => {
+                        PublicKey::Ed25519(ed25519::PublicKey { key: public.key })
+                    }
                 })
                 .collect(),
             keypairs: self
                 .keypairs
                 .iter()
                 .map(|k| match k {
-                    KeyPair::Ed25519(secret) => KeyPair::Ed25519(ed25519::SecretKey {
-                        key: secret.key.clone(),
-                    }),
+                    KeyPair::Ed25519(secret) => {
+                        KeyPair::Ed25519(ed25519::SecretKey { key: secret.key })
+                    }
                 })
                 .collect(),
         }
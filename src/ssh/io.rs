@@ -0,0 +1,142 @@
+use std::{
+    fmt, io,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+};
+
+use futures::{
+    channel::mpsc::{unbounded, UnboundedSender},
+    StreamExt,
+};
+use thrussh::{server::Handle, ChannelId, CryptoVec};
+
+use crate::vm::io::{strip_line_ending, VMIo};
+
+// Binds a VM's io to an SSH channel. Output is queued and sent by a task on the
+// server's runtime, the data the client types is fed through the sender returned by `new`.
+// Reading blocks until a line arrives, so the VM must not run on the runtime's threads.
+#[derive(Clone)]
+pub struct SshIo {
+    output: UnboundedSender<String>,
+    input: Arc<Mutex<Input>>,
+}
+
+struct Input {
+    receiver: Receiver<String>,
+    // Typed data that does not make a full line yet
+    pending: String,
+    // Whether the last line ended with \r, its \n may come with the next data
+    after_return: bool,
+}
+
+impl SshIo {
+    // Has to be called from within the tokio runtime the SSH server runs on
+    pub fn new(mut handle: Handle, channel: ChannelId) -> (Self, Sender<String>) {
+        let (output, mut outgoing) = unbounded::<String>();
+        tokio::spawn(async move {
+            while let Some(text) = outgoing.next().await {
+                let data = CryptoVec::from_slice(text.as_bytes());
+                if handle.data(channel, data).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Self::with_output(output)
+    }
+
+    fn with_output(output: UnboundedSender<String>) -> (Self, Sender<String>) {
+        let (sender, receiver) = mpsc::channel();
+        let io = Self {
+            output,
+            input: Arc::new(Mutex::new(Input {
+                receiver,
+                pending: String::new(),
+                after_return: false,
+            })),
+        };
+        (io, sender)
+    }
+}
+
+impl fmt::Debug for SshIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SshIo").finish_non_exhaustive()
+    }
+}
+
+impl VMIo for SshIo {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        // terminals need a carriage return to go back to the start of the line
+        self.output
+            .unbounded_send(text.replace('\n', "\r\n"))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "The SSH channel is closed"))
+    }
+
+    // Terminals end lines with \r, \n or both
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut input = self.input.lock().unwrap();
+        loop {
+            if !input.pending.is_empty() {
+                if input.after_return && input.pending.starts_with('\n') {
+                    input.pending.remove(0);
+                }
+                input.after_return = false;
+            }
+            if let Some(end) = input.pending.find(['\r', '\n']) {
+                let line: String = input.pending.drain(..=end).collect();
+                input.after_return = line.ends_with('\r');
+                return Ok(strip_line_ending(&line).to_owned());
+            }
+            match input.receiver.recv() {
+                Ok(data) => input.pending.push_str(&data),
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "The SSH channel stopped sending input",
+                    ))
+                }
+            }
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn VMIo> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssh_io() {
+        let (output, mut outgoing) = unbounded();
+        let (mut io, input) = SshIo::with_output(output);
+
+        io.write("Hi\nthere\n").unwrap();
+        assert_eq!(
+            outgoing.try_next().unwrap(),
+            Some("Hi\r\nthere\r\n".to_owned())
+        );
+
+        for data in ["Al", "ice\r", "\nBob\n", "\r"] {
+            input.send(data.to_owned()).unwrap();
+        }
+        assert_eq!(io.read_line().unwrap(), "Alice");
+        assert_eq!(io.read_line().unwrap(), "Bob");
+        assert_eq!(io.read_line().unwrap(), "");
+
+        drop(input);
+        drop(outgoing);
+        assert_eq!(
+            io.read_line().unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            io.write("Bye").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -6,6 +6,7 @@ use std::{collections::HashMap, sync::Arc};
 use self::config::SSHConfig;
 
 pub mod config;
+pub mod io;
 pub mod keys;
 pub mod server;
 
@@ -16,10 +17,12 @@ pub fn start_ssh_server(args: REPLArgs) {
         let ssh_config = SSHConfig::load(SSH_CONFIG_FILENAME);
         ssh_config.save(SSH_CONFIG_FILENAME);
 
-        let mut config = thrussh::server::Config::default();
-        config.connection_timeout = Some(std::time::Duration::from_secs(600));
-        config.auth_rejection_time = std::time::Duration::from_secs(3);
-        config.keys.append(&mut ssh_config.clone().keypairs);
+        let config = thrussh::server::Config {
+            connection_timeout: Some(std::time::Duration::from_secs(600)),
+            auth_rejection_time: std::time::Duration::from_secs(3),
+            keys: ssh_config.clone().keypairs,
+            ..Default::default()
+        };
 
         let config = Arc::new(config);
         let sh = Server {
@@ -27,6 +30,11 @@ pub fn start_ssh_server(args: REPLArgs) {
             clients: Arc::new(Mutex::new(HashMap::new())),
             id: 0,
             repl_mode: args.mode,
+            budget: args.budget,
+            heap_limit: args.heap_limit,
+            arithmetic_mode: args.arithmetic_mode,
+            clock: args.clock,
+            history_cap: args.history_cap,
         };
 
         let addr: &str = &format!("0.0.0.0:{}", args.ssh_port);
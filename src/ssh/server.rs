@@ -1,7 +1,13 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{mpsc::Sender, Arc},
+};
 
-use super::keys::Key;
-use crate::repl::{REPLMode, REPL};
+use super::{io::SshIo, keys::Key};
+use crate::{
+    repl::{REPLMode, REPL},
+    vm::{arithmetic::ArithmeticMode, budget::Budget, clock::Clock, memory::HeapLimit},
+};
 use futures::{executor::block_on, lock::Mutex};
 use thrussh::{
     server::{self, Auth, Session},
@@ -9,12 +15,26 @@ use thrussh::{
 };
 use thrussh_keys::key;
 
+pub struct Client {
+    pub handle: thrussh::server::Handle,
+    pub repl: REPL,
+    // Feeds what the client types to the io of the REPL's VM
+    pub input: Sender<String>,
+}
+
+type Clients = HashMap<(usize, ChannelId), Client>;
+
 #[derive(Clone)]
 pub struct Server {
     pub client_pubkey: Arc<thrussh_keys::key::PublicKey>,
-    pub clients: Arc<Mutex<HashMap<(usize, ChannelId), (thrussh::server::Handle, REPL)>>>,
+    pub clients: Arc<Mutex<Clients>>,
     pub id: usize,
     pub repl_mode: REPLMode,
+    pub budget: Budget,
+    pub heap_limit: HeapLimit,
+    pub arithmetic_mode: ArithmeticMode,
+    pub clock: Clock,
+    pub history_cap: usize,
 }
 
 impl server::Server for Server {
@@ -52,9 +72,23 @@ impl server::Handler for Server {
 
     fn channel_open_session(self, channel: ChannelId, session: Session) -> Self::FutureUnit {
         {
-            let repl = REPL::new(self.repl_mode).unwrap();
+            let mut repl = REPL::new(self.repl_mode).unwrap();
+            repl.set_budget(self.budget);
+            repl.set_heap_limit(self.heap_limit);
+            repl.set_arithmetic_mode(self.arithmetic_mode);
+            repl.set_clock(self.clock);
+            repl.set_history_cap(self.history_cap);
+            let (io, input) = SshIo::new(session.handle(), channel);
+            repl.set_io(Box::new(io));
             let mut clients = block_on(self.clients.lock());
-            clients.insert((self.id, channel), (session.handle(), repl));
+            clients.insert(
+                (self.id, channel),
+                Client {
+                    handle: session.handle(),
+                    repl,
+                    input,
+                },
+            );
         }
         self.finished(session)
     }
@@ -66,34 +100,33 @@ impl server::Handler for Server {
     fn data(self, channel: ChannelId, data: &[u8], mut session: Session) -> Self::FutureUnit {
         {
             let mut clients = block_on(self.clients.lock());
-            for ((id, channel), (ref mut s, _)) in clients.iter_mut() {
+            for ((id, channel), client) in clients.iter_mut() {
                 if *id != self.id {
-                    block_on(s.data(*channel, CryptoVec::from_slice(data))).unwrap();
+                    block_on(client.handle.data(*channel, CryptoVec::from_slice(data))).unwrap();
                 }
             }
         }
-        self.handle_input(
-            channel,
-            &mut session,
-            &String::from_utf8(data.to_vec()).unwrap(),
-        );
+        self.handle_input(channel, &mut session, core::str::from_utf8(data).unwrap());
         self.finished(session)
     }
 }
 
 impl Server {
-    fn handle_input<'a>(&self, channel: ChannelId, session: &mut Session, data: &'a str) {
+    fn handle_input(&self, channel: ChannelId, session: &mut Session, data: &str) {
         let key = Key::from(data);
         println!("{key:?}");
         self.send(channel, session, data);
+        if let Some(client) = block_on(self.clients.lock()).get(&(self.id, channel)) {
+            client.input.send(data.to_owned()).ok();
+        }
     }
 
-    fn send_line<'a>(&self, channel: ChannelId, session: &mut Session, line: &'a str) {
-        self.send(channel, session, &format!("{line}\n\r"));
+    fn send_line(&self, channel: ChannelId, session: &mut Session, line: &str) {
+        self.send(channel, session, &format!("{line}\r\n"));
         session.flush().unwrap();
     }
 
-    fn send<'a>(&self, channel: ChannelId, session: &mut Session, data: &'a str) {
-        session.data(channel, CryptoVec::from_slice(&data.as_bytes()));
+    fn send(&self, channel: ChannelId, session: &mut Session, data: &str) {
+        session.data(channel, CryptoVec::from_slice(data.as_bytes()));
     }
 }
@@ -0,0 +1,100 @@
+use std::{fmt, str::FromStr};
+
+use serde_derive::{Deserialize, Serialize};
+
+// What integer arithmetic does when its result does not fit in 32 bits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArithmeticMode {
+    #[default]
+    Wrapping,
+    Saturating,
+    Trapping,
+}
+
+impl ArithmeticMode {
+    // The value to store in the destination register, None if the VM must fault
+    pub fn resolve(self, outcome: &Outcome) -> Option<i32> {
+        match (self, outcome.overflowed) {
+            (_, false) | (ArithmeticMode::Wrapping, true) => Some(outcome.wrapped),
+            (ArithmeticMode::Saturating, true) => Some(outcome.saturated),
+            (ArithmeticMode::Trapping, true) => None,
+        }
+    }
+}
+
+impl FromStr for ArithmeticMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "wrapping" => Ok(ArithmeticMode::Wrapping),
+            "saturating" => Ok(ArithmeticMode::Saturating),
+            "trapping" => Ok(ArithmeticMode::Trapping),
+            _ => Err(format!("Unknown arithmetic mode \"{s}\"")),
+        }
+    }
+}
+
+impl fmt::Display for ArithmeticMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ArithmeticMode::Wrapping => "wrapping",
+            ArithmeticMode::Saturating => "saturating",
+            ArithmeticMode::Trapping => "trapping",
+        })
+    }
+}
+
+// Every possible result of an integer operation, the arithmetic mode picks one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    pub wrapped: i32,
+    pub saturated: i32,
+    pub overflowed: bool,
+    pub remainder: Option<i32>,
+}
+
+impl Outcome {
+    pub fn new((wrapped, overflowed): (i32, bool), saturated: i32) -> Self {
+        Self {
+            wrapped,
+            saturated,
+            overflowed,
+            remainder: None,
+        }
+    }
+
+    // For operations that cannot overflow
+    pub fn exact(value: i32) -> Self {
+        Self::new((value, false), value)
+    }
+
+    pub fn with_remainder(mut self, remainder: i32) -> Self {
+        self.remainder = Some(remainder);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let outcome = Outcome::new(i32::MAX.overflowing_add(1), i32::MAX.saturating_add(1));
+        assert_eq!(ArithmeticMode::Wrapping.resolve(&outcome), Some(i32::MIN));
+        assert_eq!(ArithmeticMode::Saturating.resolve(&outcome), Some(i32::MAX));
+        assert_eq!(ArithmeticMode::Trapping.resolve(&outcome), None);
+        assert_eq!(
+            ArithmeticMode::Trapping.resolve(&Outcome::exact(4)),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!("Saturating".parse(), Ok(ArithmeticMode::Saturating));
+        assert_eq!("trapping".parse(), Ok(ArithmeticMode::Trapping));
+        assert!("checked".parse::<ArithmeticMode>().is_err());
+    }
+}
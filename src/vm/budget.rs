@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+// Limits how much a single call to `VM::run` or `VM::resume` may execute.
+// Every call gets the whole budget again, so an exhausted VM can simply be resumed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub max_instructions: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl Budget {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn instructions(max_instructions: u64) -> Self {
+        Self {
+            max_instructions: Some(max_instructions),
+            timeout: None,
+        }
+    }
+
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            max_instructions: None,
+            timeout: Some(timeout),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_instructions.is_none() && self.timeout.is_none()
+    }
+}
@@ -0,0 +1,80 @@
+use std::{fmt, str::FromStr, thread, time::Duration};
+
+use serde_derive::{Deserialize, Serialize};
+
+// What SLP and SLPS wait on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Clock {
+    // Sleeps block the thread
+    #[default]
+    Real,
+    // Sleeps return at once and only advance the simulated time
+    Virtual {
+        elapsed: Duration,
+    },
+}
+
+impl Clock {
+    pub fn virtual_clock() -> Self {
+        Clock::Virtual {
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn sleep(&mut self, duration: Duration) {
+        match self {
+            Clock::Real => thread::sleep(duration),
+            Clock::Virtual { elapsed } => *elapsed += duration,
+        }
+    }
+
+    // Time slept so far, None for the real clock
+    pub fn virtual_time(&self) -> Option<Duration> {
+        match self {
+            Clock::Real => None,
+            Clock::Virtual { elapsed } => Some(*elapsed),
+        }
+    }
+}
+
+impl FromStr for Clock {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "real" => Ok(Clock::Real),
+            "virtual" => Ok(Clock::virtual_clock()),
+            _ => Err(format!("Unknown clock \"{s}\"")),
+        }
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Clock::Real => f.write_str("real"),
+            Clock::Virtual { elapsed } => write!(f, "virtual ({elapsed:?} elapsed)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_sleep() {
+        let mut clock = Clock::virtual_clock();
+        clock.sleep(Duration::from_secs(3600));
+        clock.sleep(Duration::from_millis(5));
+        assert_eq!(clock.virtual_time(), Some(Duration::from_millis(3_600_005)));
+        assert_eq!(Clock::Real.virtual_time(), None);
+    }
+
+    #[test]
+    fn test_parse_clock() {
+        assert_eq!("Virtual".parse(), Ok(Clock::virtual_clock()));
+        assert_eq!("real".parse(), Ok(Clock::Real));
+        assert!("wall".parse::<Clock>().is_err());
+    }
+}
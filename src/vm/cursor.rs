@@ -5,13 +5,15 @@ use crate::instruction::Opcode;
 pub trait ProgramCursor {
     fn next_8_bits(&mut self) -> Option<u8>;
     fn next_16_bits(&mut self) -> Option<u16>;
+    fn next_32_bits(&mut self) -> Option<u32>;
+    fn next_64_bits(&mut self) -> Option<u64>;
     fn get_position(&self) -> usize;
 
     fn read_opcode(&mut self) -> Option<Opcode> {
         let byte = self.next_8_bits();
-        let opcode = byte.map(|byte| Opcode::from(byte));
+        let opcode = byte.map(Opcode::from);
         // println!("{}: {:?} => {opcode:?}", self.get_position(), byte);
-        return opcode;
+        opcode
     }
 
     fn read_index(&mut self) -> Option<usize> {
@@ -47,6 +49,26 @@ where
         }
     }
 
+    fn next_32_bits(&mut self) -> Option<u32> {
+        let mut buf = [0; 4];
+        let read = self.read(&mut buf).unwrap_or(0);
+        if read == 4 {
+            Some(u32::from_be_bytes(buf))
+        } else {
+            None
+        }
+    }
+
+    fn next_64_bits(&mut self) -> Option<u64> {
+        let mut buf = [0; 8];
+        let read = self.read(&mut buf).unwrap_or(0);
+        if read == 8 {
+            Some(u64::from_be_bytes(buf))
+        } else {
+            None
+        }
+    }
+
     fn get_position(&self) -> usize {
         self.position() as usize
     }
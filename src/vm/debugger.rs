@@ -0,0 +1,130 @@
+use std::time::Instant;
+
+use crate::assembler::{
+    symbols::{SymbolTable, SymbolType},
+    PIE_HEADER_LENGTH,
+};
+
+use super::{error::VMError, events::VMEventType, operator::Operator, VM};
+
+// What happened after asking the VM to execute
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepResult {
+    // The instruction ran and the program can go on
+    Continued,
+    Halted(u32),
+    Faulted(VMError),
+    // Stopped before the instruction at this code offset
+    Breakpoint(usize),
+    BudgetExhausted { executed: u64 },
+}
+
+impl VM {
+    // Executes exactly one instruction, ignoring breakpoints and the budget
+    pub fn run_once(&mut self) -> StepResult {
+        self.paused_at = None;
+        let pc = self.program_cursor.position() as usize;
+        let recording = self.history.is_recording();
+        let before =
+            (recording || self.tracer.is_some()).then_some((self.registers, self.float_registers));
+        let delta = recording.then(|| self.begin_delta(pc));
+        let profiling = self
+            .profile
+            .as_ref()
+            .map(|_| (self.profile_stack(pc), Instant::now()));
+
+        let result = self.execute_instruction();
+        if let Some((stack, started)) = profiling {
+            self.profile_instruction(pc, stack, started);
+        }
+        self.history.executed += 1;
+        if let Some((registers, float_registers)) = before {
+            self.trace(pc, &registers, &float_registers, result.is_ok());
+            if let Some(delta) = delta {
+                self.record(delta, &registers, &float_registers);
+            }
+        }
+        match result {
+            Ok(None) => StepResult::Continued,
+            Ok(Some(code)) => {
+                self.push_event(VMEventType::GracefulStop { code });
+                StepResult::Halted(code)
+            }
+            Err(error) => {
+                self.crash(error.clone());
+                StepResult::Faulted(error)
+            }
+        }
+    }
+
+    // Executes until the program stops, a breakpoint is reached or the budget runs out.
+    // Every call gets a fresh budget and never stops on the breakpoint it is paused at.
+    pub fn continue_execution(&mut self) -> StepResult {
+        let deadline = self.budget.timeout.map(|timeout| Instant::now() + timeout);
+        let mut executed = 0;
+
+        loop {
+            let out_of_instructions = self
+                .budget
+                .max_instructions
+                .is_some_and(|max| executed >= max);
+            let out_of_time = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            if out_of_instructions || out_of_time {
+                let pc = self.program_cursor.position() as usize;
+                self.push_event(VMEventType::BudgetExhausted { executed, pc });
+                return StepResult::BudgetExhausted { executed };
+            }
+
+            if !self.breakpoints.is_empty() {
+                if let Some(offset) = self.code_offset() {
+                    let resuming = executed == 0 && self.paused_at == Some(offset);
+                    if !resuming && self.breakpoints.contains(&offset) {
+                        self.paused_at = Some(offset);
+                        self.push_event(VMEventType::BreakpointHit { offset });
+                        return StepResult::Breakpoint(offset);
+                    }
+                }
+            }
+
+            executed += 1;
+            match self.run_once() {
+                StepResult::Continued => continue,
+                result => return result,
+            }
+        }
+    }
+
+    // Position of the next instruction, relative to the start of the code like labels are
+    pub fn code_offset(&self) -> Option<usize> {
+        let offset = self.get_starting_offset().ok()?;
+        (self.program_cursor.position() as usize).checked_sub(PIE_HEADER_LENGTH + offset)
+    }
+
+    // Returns false if there already was a breakpoint at this offset
+    pub fn add_breakpoint(&mut self, offset: usize) -> bool {
+        self.breakpoints.insert(offset)
+    }
+
+    // Resolves a code label to its offset and breaks there
+    pub fn add_label_breakpoint(&mut self, label: &str, symbols: &SymbolTable) -> Option<usize> {
+        if symbols.symbol_type(label)? != SymbolType::Label {
+            return None;
+        }
+        let offset = symbols.symbol_value(label)?;
+        self.add_breakpoint(offset);
+        Some(offset)
+    }
+
+    // Returns false if there was no breakpoint at this offset
+    pub fn remove_breakpoint(&mut self, offset: usize) -> bool {
+        self.breakpoints.remove(&offset)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+}
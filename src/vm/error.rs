@@ -0,0 +1,232 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::instruction::Opcode;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VMError {
+    InvalidHeader,
+    IllegalOpcode {
+        pc: usize,
+        opcode: u8,
+    },
+    MissingOperand {
+        pc: usize,
+        opcode: Opcode,
+    },
+    InvalidRegister {
+        pc: usize,
+        opcode: Opcode,
+        register: usize,
+    },
+    InvalidHeapIndex {
+        pc: usize,
+        opcode: Opcode,
+        index: usize,
+    },
+    InvalidHandle {
+        pc: usize,
+        opcode: Opcode,
+        handle: i32,
+    },
+    InvalidUtf8 {
+        pc: usize,
+        opcode: Opcode,
+        index: usize,
+    },
+    WrongPartitionSize {
+        pc: usize,
+        opcode: Opcode,
+        index: usize,
+        size: usize,
+        expected: usize,
+    },
+    HeapFull {
+        pc: usize,
+        opcode: Opcode,
+        requested: usize,
+        available: usize,
+    },
+    ReadOnlyPartition {
+        pc: usize,
+        opcode: Opcode,
+        index: usize,
+    },
+    OutOfBounds {
+        pc: usize,
+        opcode: Opcode,
+        index: usize,
+        offset: i32,
+        size: usize,
+    },
+    DivisionByZero {
+        pc: usize,
+        opcode: Opcode,
+    },
+    ArithmeticOverflow {
+        pc: usize,
+        opcode: Opcode,
+    },
+    InvalidJumpTarget {
+        pc: usize,
+        opcode: Opcode,
+        target: i64,
+    },
+    InvalidAllocation {
+        pc: usize,
+        opcode: Opcode,
+        bytes: i32,
+    },
+    Io {
+        pc: usize,
+        opcode: Opcode,
+        error: String,
+    },
+    StackOverflow {
+        pc: usize,
+        opcode: Opcode,
+    },
+    StackUnderflow {
+        pc: usize,
+        opcode: Opcode,
+    },
+}
+
+impl VMError {
+    // Position of the faulting instruction in the program, header included
+    pub fn pc(&self) -> usize {
+        match *self {
+            VMError::InvalidHeader => 0,
+            VMError::IllegalOpcode { pc, .. }
+            | VMError::MissingOperand { pc, .. }
+            | VMError::InvalidRegister { pc, .. }
+            | VMError::InvalidHeapIndex { pc, .. }
+            | VMError::InvalidHandle { pc, .. }
+            | VMError::InvalidUtf8 { pc, .. }
+            | VMError::WrongPartitionSize { pc, .. }
+            | VMError::HeapFull { pc, .. }
+            | VMError::ReadOnlyPartition { pc, .. }
+            | VMError::OutOfBounds { pc, .. }
+            | VMError::DivisionByZero { pc, .. }
+            | VMError::ArithmeticOverflow { pc, .. }
+            | VMError::InvalidJumpTarget { pc, .. }
+            | VMError::InvalidAllocation { pc, .. }
+            | VMError::Io { pc, .. }
+            | VMError::StackOverflow { pc, .. }
+            | VMError::StackUnderflow { pc, .. } => pc,
+        }
+    }
+
+    // Decoded opcode of the faulting instruction, if it was a legal one
+    pub fn opcode(&self) -> Option<Opcode> {
+        match *self {
+            VMError::InvalidHeader | VMError::IllegalOpcode { .. } => None,
+            VMError::MissingOperand { opcode, .. }
+            | VMError::InvalidRegister { opcode, .. }
+            | VMError::InvalidHeapIndex { opcode, .. }
+            | VMError::InvalidHandle { opcode, .. }
+            | VMError::InvalidUtf8 { opcode, .. }
+            | VMError::WrongPartitionSize { opcode, .. }
+            | VMError::HeapFull { opcode, .. }
+            | VMError::ReadOnlyPartition { opcode, .. }
+            | VMError::OutOfBounds { opcode, .. }
+            | VMError::DivisionByZero { opcode, .. }
+            | VMError::ArithmeticOverflow { opcode, .. }
+            | VMError::InvalidJumpTarget { opcode, .. }
+            | VMError::InvalidAllocation { opcode, .. }
+            | VMError::Io { opcode, .. }
+            | VMError::StackOverflow { opcode, .. }
+            | VMError::StackUnderflow { opcode, .. } => Some(opcode),
+        }
+    }
+}
+
+impl fmt::Display for VMError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VMError::InvalidHeader => f.write_str("The program header was incorrect"),
+            VMError::IllegalOpcode { pc, opcode } => {
+                write!(f, "Illegal opcode {opcode} encountered at {pc}")
+            }
+            VMError::MissingOperand { pc, opcode } => {
+                write!(f, "{opcode:?} at {pc} is missing an operand")
+            }
+            VMError::InvalidRegister {
+                pc,
+                opcode,
+                register,
+            } => write!(f, "{opcode:?} at {pc} used invalid register ${register}"),
+            VMError::InvalidHeapIndex { pc, opcode, index } => write!(
+                f,
+                "{opcode:?} at {pc} used heap index {index} which has no partition"
+            ),
+            VMError::InvalidHandle { pc, opcode, handle } => write!(
+                f,
+                "{opcode:?} at {pc} used heap handle {handle} which has no partition"
+            ),
+            VMError::InvalidUtf8 { pc, opcode, index } => write!(
+                f,
+                "{opcode:?} at {pc} read heap index {index} which is not valid UTF-8"
+            ),
+            VMError::WrongPartitionSize {
+                pc,
+                opcode,
+                index,
+                size,
+                expected,
+            } => write!(
+                f,
+                "{opcode:?} at {pc} used heap index {index} which holds {size} bytes instead of {expected}"
+            ),
+            VMError::HeapFull {
+                pc,
+                opcode,
+                requested,
+                available,
+            } => write!(
+                f,
+                "{opcode:?} at {pc} needed {requested} more heap bytes but only {available} are left"
+            ),
+            VMError::ReadOnlyPartition { pc, opcode, index } => write!(
+                f,
+                "{opcode:?} at {pc} wrote to heap index {index} which is read-only"
+            ),
+            VMError::OutOfBounds {
+                pc,
+                opcode,
+                index,
+                offset,
+                size,
+            } => write!(
+                f,
+                "{opcode:?} at {pc} accessed offset {offset} of heap index {index} which holds {size} bytes"
+            ),
+            VMError::DivisionByZero { pc, opcode } => {
+                write!(f, "{opcode:?} at {pc} divided by zero")
+            }
+            VMError::ArithmeticOverflow { pc, opcode } => {
+                write!(f, "{opcode:?} at {pc} overflowed a 32 bit integer")
+            }
+            VMError::InvalidJumpTarget { pc, opcode, target } => write!(
+                f,
+                "{opcode:?} at {pc} jumped to {target} which is before the program start"
+            ),
+            VMError::InvalidAllocation { pc, opcode, bytes } => {
+                write!(f, "{opcode:?} at {pc} tried to allocate {bytes} bytes")
+            }
+            VMError::Io {
+                pc,
+                opcode,
+                ref error,
+            } => write!(f, "{opcode:?} at {pc} failed to use the VM's io: {error}"),
+            VMError::StackOverflow { pc, opcode } => {
+                write!(f, "{opcode:?} at {pc} overflowed the stack")
+            }
+            VMError::StackUnderflow { pc, opcode } => {
+                write!(f, "{opcode:?} at {pc} found an empty stack")
+            }
+        }
+    }
+}
+
+impl Error for VMError {}
@@ -1,11 +1,19 @@
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-#[derive(Clone, Debug)]
+use super::error::VMError;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum VMEventType {
     Start,
     GracefulStop { code: u32 },
-    Crash,
+    Crash { error: VMError, pc: usize },
+    // The budget ran out before the program stopped, `pc` is the next instruction
+    BudgetExhausted { executed: u64, pc: usize },
+    // Paused before the instruction at this code offset
+    BreakpointHit { offset: usize },
 }
 
 #[allow(unused)]
@@ -13,6 +21,8 @@ pub enum VMEventType {
 pub struct VMEvent {
     event: VMEventType,
     at: DateTime<Utc>,
+    // Time the program slept when it runs on a virtual clock
+    virtual_time: Option<Duration>,
     application_id: Uuid,
 }
 
@@ -22,6 +32,20 @@ impl VMEvent {
             event,
             application_id,
             at: Utc::now(),
+            virtual_time: None,
         }
     }
+
+    pub fn at_virtual_time(mut self, virtual_time: Option<Duration>) -> Self {
+        self.virtual_time = virtual_time;
+        self
+    }
+
+    pub fn event(&self) -> &VMEventType {
+        &self.event
+    }
+
+    pub fn virtual_time(&self) -> Option<Duration> {
+        self.virtual_time
+    }
 }
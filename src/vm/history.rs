@@ -0,0 +1,245 @@
+use std::{collections::VecDeque, io::Cursor};
+
+use crate::instruction::{Opcode, Operand};
+
+use super::{clock::Clock, memory::HeapMark, VM};
+
+// How many instructions the REPL remembers by default
+pub const DEFAULT_HISTORY_CAP: usize = 10_000;
+
+// What one instruction changed, enough to undo it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    // Where the instruction started
+    pub pc: usize,
+    // Previous values of the registers the instruction wrote
+    pub registers: Vec<(usize, i32)>,
+    pub float_registers: Vec<(usize, f64)>,
+    pub remainder: i32,
+    pub equal_flag: bool,
+    pub overflow_flag: bool,
+    pub clock: Clock,
+    // Only kept for the instructions that can change them
+    pub heap: Option<HeapMark>,
+    pub stack: Option<StackMark<i32>>,
+    pub return_addresses: Option<StackMark<usize>>,
+}
+
+// Depth and top of a stack, enough to undo one push or pop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackMark<T> {
+    len: usize,
+    top: Option<T>,
+}
+
+impl<T: Copy> StackMark<T> {
+    fn new(stack: &[T]) -> Self {
+        Self {
+            len: stack.len(),
+            top: stack.last().copied(),
+        }
+    }
+
+    fn restore(self, stack: &mut Vec<T>) {
+        stack.truncate(self.len);
+        if stack.len() < self.len {
+            stack.extend(self.top);
+        }
+    }
+}
+
+// The last instructions the VM executed, oldest first. A cap of 0 disables recording.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    cap: usize,
+    deltas: VecDeque<Delta>,
+    // Instructions executed since the program started, recorded or not
+    pub(super) executed: u64,
+}
+
+impl History {
+    pub fn is_recording(&self) -> bool {
+        self.cap > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    fn push(&mut self, delta: Delta) {
+        if self.deltas.len() >= self.cap {
+            self.deltas.pop_front();
+        }
+        self.deltas.push_back(delta);
+    }
+
+    fn clear(&mut self) {
+        self.deltas.clear();
+        self.executed = 0;
+    }
+}
+
+fn writes_heap(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::ALOC
+            | Opcode::ASKS
+            | Opcode::GRPS
+            | Opcode::STI
+            | Opcode::NEWP
+            | Opcode::FREE
+            | Opcode::ASKSR
+            | Opcode::GRPSR
+            | Opcode::STB
+            | Opcode::STW
+    )
+}
+
+fn writes_stack(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::PUSH | Opcode::POP)
+}
+
+fn writes_return_addresses(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::CALL | Opcode::CALLT | Opcode::RET)
+}
+
+impl VM {
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    // Drops the oldest instructions if the new cap is smaller
+    pub fn set_history_cap(&mut self, cap: usize) {
+        self.history.cap = cap;
+        while self.history.deltas.len() > cap {
+            self.history.deltas.pop_front();
+        }
+    }
+
+    // Number of instructions executed since the program started
+    pub fn executed(&self) -> u64 {
+        self.history.executed
+    }
+
+    pub(super) fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    // Saves what the instruction at `pc` is about to overwrite, registers aside
+    pub(super) fn begin_delta(&self, pc: usize) -> Delta {
+        let code = self.program_cursor.get_ref();
+        let opcode = match code.get(pc) {
+            Some(byte) => Opcode::from(*byte),
+            None => Opcode::IGL,
+        };
+
+        Delta {
+            pc,
+            registers: Vec::new(),
+            float_registers: Vec::new(),
+            remainder: self.remainder,
+            equal_flag: self.equal_flag,
+            overflow_flag: self.overflow_flag,
+            clock: self.clock,
+            heap: writes_heap(opcode).then(|| {
+                let operands = opcode
+                    .decode_operands(&mut Cursor::new(&code[pc + 1..]))
+                    .unwrap_or_default();
+                self.heap_mark(opcode, &operands)
+            }),
+            stack: writes_stack(opcode).then(|| StackMark::new(&self.stack)),
+            return_addresses: writes_return_addresses(opcode)
+                .then(|| StackMark::new(&self.return_addresses)),
+        }
+    }
+
+    // Keeps only the partition the instruction writes, or the bytes for STB and STW
+    fn heap_mark(&self, opcode: Opcode, operands: &[Operand]) -> HeapMark {
+        let heap = &self.memory_heap;
+        let written = opcode
+            .written_heap_operand()
+            .and_then(|position| VM::partition_operand(operands.get(position), &self.registers));
+        let offset = match operands.get(2) {
+            Some(Operand::Register(register)) => self
+                .registers
+                .get(*register as usize)
+                .and_then(|offset| usize::try_from(*offset).ok()),
+            _ => None,
+        };
+
+        match (opcode, written, offset) {
+            (Opcode::NEWP, _, _) => heap.mark_partition(heap.next_id()),
+            (Opcode::STB, Some(id), Some(start)) => heap.mark_range(id, start..start + 1),
+            (Opcode::STW, Some(id), Some(start)) => heap.mark_range(id, start..start + 4),
+            // a negative offset faults before anything is written
+            (Opcode::STB | Opcode::STW, _, _) => heap.mark(),
+            (_, Some(id), _) => heap.mark_partition(id),
+            _ => heap.mark(),
+        }
+    }
+
+    // Completes the delta with the registers that changed since `registers` were saved
+    pub(super) fn record(
+        &mut self,
+        mut delta: Delta,
+        registers: &[i32; 32],
+        float_registers: &[f64; 32],
+    ) {
+        delta.registers = (0..registers.len())
+            .filter(|r| registers[*r] != self.registers[*r])
+            .map(|r| (r, registers[r]))
+            .collect();
+        delta.float_registers = (0..float_registers.len())
+            .filter(|r| float_registers[*r].to_bits() != self.float_registers[*r].to_bits())
+            .map(|r| (r, float_registers[r]))
+            .collect();
+        self.history.push(delta);
+    }
+
+    // Undoes the last recorded instruction, returns false when there is nothing left to undo
+    pub fn step_back(&mut self) -> bool {
+        let delta = match self.history.deltas.pop_back() {
+            Some(delta) => delta,
+            None => return false,
+        };
+
+        for (register, value) in delta.registers {
+            self.registers[register] = value;
+        }
+        for (register, value) in delta.float_registers {
+            self.float_registers[register] = value;
+        }
+        self.remainder = delta.remainder;
+        self.equal_flag = delta.equal_flag;
+        self.overflow_flag = delta.overflow_flag;
+        self.clock = delta.clock;
+        if let Some(heap) = delta.heap {
+            self.memory_heap.restore(heap);
+        }
+        if let Some(stack) = delta.stack {
+            stack.restore(&mut self.stack);
+        }
+        if let Some(return_addresses) = delta.return_addresses {
+            return_addresses.restore(&mut self.return_addresses);
+        }
+        self.program_cursor.set_position(delta.pc as u64);
+        self.history.executed = self.history.executed.saturating_sub(1);
+        // continuing must not stop on a breakpoint right where we stepped back to
+        self.paused_at = self.code_offset();
+        true
+    }
+
+    // Steps back until only `executed` instructions ran, or the history runs out.
+    // Returns how many instructions were undone.
+    pub fn rewind(&mut self, executed: u64) -> u64 {
+        let mut undone = 0;
+        while self.history.executed > executed && self.step_back() {
+            undone += 1;
+        }
+        undone
+    }
+}
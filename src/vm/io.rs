@@ -0,0 +1,119 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    io::{self, stdin, stdout, Write},
+    sync::{Arc, Mutex},
+};
+
+// Everything a program prints or asks for goes through the VM's io backend
+pub trait VMIo: Debug + Send {
+    // Writes text as-is, callers add the line feeds they need
+    fn write(&mut self, text: &str) -> io::Result<()>;
+    // Reads one line of input, without its line ending
+    fn read_line(&mut self) -> io::Result<String>;
+    fn box_clone(&self) -> Box<dyn VMIo>;
+}
+
+impl Clone for Box<dyn VMIo> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+pub fn strip_line_ending(line: &str) -> &str {
+    line.trim_end_matches(['\r', '\n'])
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StdIo;
+
+impl VMIo for StdIo {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        let mut stdout = stdout();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if stdin().read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Reached the end of stdin",
+            ));
+        }
+        Ok(strip_line_ending(&line).to_owned())
+    }
+
+    fn box_clone(&self) -> Box<dyn VMIo> {
+        Box::new(self.clone())
+    }
+}
+
+// Clones share the same buffers, so a test can keep one handle and give another to the VM
+#[derive(Debug, Clone, Default)]
+pub struct MemoryIo {
+    input: Arc<Mutex<VecDeque<String>>>,
+    output: Arc<Mutex<String>>,
+}
+
+impl MemoryIo {
+    pub fn new(input: &[&str]) -> Self {
+        let io = Self::default();
+        for line in input {
+            io.push_input(line);
+        }
+        io
+    }
+
+    pub fn push_input(&self, line: &str) {
+        self.input.lock().unwrap().push_back(line.to_owned());
+    }
+
+    pub fn output(&self) -> String {
+        self.output.lock().unwrap().clone()
+    }
+}
+
+impl VMIo for MemoryIo {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.output.lock().unwrap().push_str(text);
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        self.input
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "No more scripted input"))
+    }
+
+    fn box_clone(&self) -> Box<dyn VMIo> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_io() {
+        let io = MemoryIo::new(&["first"]);
+        let mut vm_io: Box<dyn VMIo> = Box::new(io.clone());
+        vm_io.write("Hello\n").unwrap();
+        assert_eq!(vm_io.read_line().unwrap(), "first");
+        assert!(vm_io.read_line().is_err());
+        io.push_input("second");
+        assert_eq!(vm_io.read_line().unwrap(), "second");
+        assert_eq!(io.output(), "Hello\n");
+    }
+
+    #[test]
+    fn test_strip_line_ending() {
+        assert_eq!(strip_line_ending("Alice\r\n"), "Alice");
+        assert_eq!(strip_line_ending("Bob\n"), "Bob");
+        assert_eq!(strip_line_ending(""), "");
+    }
+}
@@ -1,95 +1,365 @@
 use std::{
-    io::{Cursor, Read},
+    collections::BTreeSet,
+    error::Error,
+    fmt,
+    io::{self, Cursor, Read},
     ops::Range,
 };
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
+// Each partition is stored as its end offset followed by a flags byte
+pub const PARTITION_ENTRY_SIZE: usize = 5;
+
+const READ_ONLY: u8 = 1;
+
+// How far a heap may grow. ALOC and growing on demand both stop at `max_size`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapLimit {
+    pub max_size: Option<usize>,
+    // Writes that do not fit make room for themselves instead of failing
+    pub grow: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapFull {
+    pub requested: usize,
+    pub available: usize,
+}
+
+impl fmt::Display for HeapFull {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} more bytes were needed but only {} are left",
+            self.requested, self.available
+        )
+    }
+}
+
+impl Error for HeapFull {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapError {
+    Full(HeapFull),
+    // The partition was never added or has been freed
+    UnknownPartition(usize),
+}
+
+impl From<HeapFull> for HeapError {
+    fn from(error: HeapFull) -> Self {
+        HeapError::Full(error)
+    }
+}
+
+impl fmt::Display for HeapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeapError::Full(error) => error.fmt(f),
+            HeapError::UnknownPartition(id) => write!(f, "There is no partition {id}"),
+        }
+    }
+}
+
+impl Error for HeapError {}
+
+// The heap before an instruction changed it, taken by the VM's history
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeapMark {
+    partitions: usize,
+    capacity: usize,
+    saved: Option<SavedBytes>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SavedBytes {
+    // A whole partition that is about to be edited, freed or reused
+    Partition {
+        id: usize,
+        bytes: Vec<u8>,
+        freed: bool,
+        read_only: bool,
+    },
+    // Bytes about to be overwritten in place
+    Range {
+        id: usize,
+        start: usize,
+        bytes: Vec<u8>,
+    },
+}
+
 // This file was originially generated by ChatGPT (after some discussion)
 
 // I had given this struct
 #[derive(Debug, Clone, PartialEq)]
 pub struct MemoryHeap {
-    data: Vec<u8>,
-    partitions: Vec<Range<usize>>,
+    // Every partition owns its bytes, so resizing one never moves the others
+    partitions: Vec<Vec<u8>>,
+    // Bytes stored over all partitions
+    size: usize,
+    // Tombstones of the partitions freed at runtime, so the other ids stay valid
+    freed: BTreeSet<usize>,
+    // Partitions from .rodata, the VM refuses to write them
+    read_only: BTreeSet<usize>,
+    // Bytes the partitions may use in total, reserved by the assembler and ALOC
+    capacity: usize,
+    limit: HeapLimit,
 }
 
 impl MemoryHeap {
     pub fn new(max_size: usize) -> Self {
         Self {
-            data: Vec::with_capacity(max_size),
             partitions: Vec::new(),
+            size: 0,
+            freed: BTreeSet::new(),
+            read_only: BTreeSet::new(),
+            capacity: max_size,
+            limit: HeapLimit::default(),
+        }
+    }
+
+    pub fn limit(&self) -> HeapLimit {
+        self.limit
+    }
+
+    // Also gives back the reserved room above the new maximum, the data itself always stays
+    pub fn set_limit(&mut self, limit: HeapLimit) {
+        if let Some(max_size) = limit.max_size {
+            self.capacity = self.capacity.min(max_size.max(self.size));
         }
+        self.limit = limit;
     }
 
-    pub fn add(&mut self, bytes: Vec<u8>) -> usize {
-        if self.data.len() + bytes.len() > self.data.capacity() {
-            panic!("Memory heap exceeded maximum size");
+    // Makes sure `additional` more bytes fit, growing the capacity if the limit allows it
+    fn make_room(&mut self, additional: usize) -> Result<(), HeapFull> {
+        let needed = self.size + additional;
+        if needed <= self.capacity {
+            return Ok(());
         }
-        let start = self.data.len();
-        self.data.extend(bytes);
-        let end = self.data.len();
-        self.partitions.push(start..end);
-        self.partitions.len() - 1
+        let room = match self.limit {
+            HeapLimit {
+                grow: true,
+                max_size,
+            } => max_size.unwrap_or(usize::MAX).max(self.capacity),
+            HeapLimit { grow: false, .. } => self.capacity,
+        };
+        if needed > room {
+            return Err(HeapFull {
+                requested: additional,
+                available: room - self.size,
+            });
+        }
+        self.capacity = needed;
+        Ok(())
     }
 
-    pub fn remove(&mut self, id: usize) {
-        let range = &self.partitions.remove(id);
-        self.delete(range.clone());
+    pub fn add(&mut self, bytes: Vec<u8>) -> Result<usize, HeapFull> {
+        self.make_room(bytes.len())?;
+        self.size += bytes.len();
+        self.partitions.push(bytes);
+        Ok(self.partitions.len() - 1)
     }
 
-    fn delete(&mut self, range: Range<usize>) {
-        self.data.drain(range.clone());
+    // Like add, but reuses the lowest freed id first
+    pub fn insert(&mut self, bytes: Vec<u8>) -> Result<usize, HeapFull> {
+        let id = match self.freed.first() {
+            Some(id) => *id,
+            None => return self.add(bytes),
+        };
+        self.make_room(bytes.len())?;
+        self.freed.remove(&id);
+        self.size += bytes.len();
+        self.partitions[id] = bytes;
+        Ok(id)
+    }
 
-        for partition in &mut self.partitions {
-            if partition.start > range.end {
-                *partition = partition.start - range.len()..partition.end - range.len();
+    // Releases the bytes of a partition and leaves a tombstone, returns false if it did not exist
+    pub fn free(&mut self, id: usize) -> bool {
+        match self.partitions.get_mut(id) {
+            Some(partition) if !self.freed.contains(&id) => {
+                self.size -= partition.len();
+                *partition = Vec::new();
             }
+            _ => return false,
         }
+        self.freed.insert(id);
+        self.read_only.remove(&id);
+        true
+    }
+
+    pub fn set_read_only(&mut self, id: usize) {
+        self.read_only.insert(id);
+    }
+
+    pub fn is_read_only(&self, id: usize) -> bool {
+        self.read_only.contains(&id)
+    }
+
+    pub fn freed(&self) -> impl Iterator<Item = usize> + '_ {
+        self.freed.iter().copied()
+    }
+
+    // The id the next insert will use
+    pub fn next_id(&self) -> usize {
+        self.freed.first().copied().unwrap_or(self.partitions.len())
+    }
+
+    // Enough to undo added partitions and a changed capacity
+    pub fn mark(&self) -> HeapMark {
+        HeapMark {
+            partitions: self.partitions.len(),
+            capacity: self.capacity,
+            saved: None,
+        }
+    }
+
+    // Also keeps a copy of partition `id`, for an edit, a free or an insert reusing it
+    pub fn mark_partition(&self, id: usize) -> HeapMark {
+        let saved = self.partitions.get(id).map(|bytes| SavedBytes::Partition {
+            id,
+            bytes: bytes.clone(),
+            freed: self.freed.contains(&id),
+            read_only: self.is_read_only(id),
+        });
+        HeapMark {
+            saved,
+            ..self.mark()
+        }
+    }
+
+    // Only keeps the bytes in `range` of partition `id`, for a write in place
+    pub fn mark_range(&self, id: usize, range: Range<usize>) -> HeapMark {
+        let saved = self
+            .get_slice(id)
+            .and_then(|bytes| bytes.get(range.clone()))
+            .map(|bytes| SavedBytes::Range {
+                id,
+                start: range.start,
+                bytes: bytes.to_vec(),
+            });
+        HeapMark {
+            saved,
+            ..self.mark()
+        }
+    }
+
+    // Puts the heap back as it was when `mark` was taken, if only one instruction ran since
+    pub fn restore(&mut self, mark: HeapMark) {
+        match mark.saved {
+            Some(SavedBytes::Partition {
+                id,
+                bytes,
+                freed,
+                read_only,
+            }) => {
+                if let Some(partition) = self.partitions.get_mut(id) {
+                    self.size = self.size - partition.len() + bytes.len();
+                    *partition = bytes;
+                }
+                if freed {
+                    self.freed.insert(id);
+                } else {
+                    self.freed.remove(&id);
+                }
+                if read_only {
+                    self.read_only.insert(id);
+                } else {
+                    self.read_only.remove(&id);
+                }
+            }
+            Some(SavedBytes::Range { id, start, bytes }) => {
+                if let Some(range) = self
+                    .partitions
+                    .get_mut(id)
+                    .and_then(|partition| partition.get_mut(start..start + bytes.len()))
+                {
+                    range.copy_from_slice(&bytes);
+                }
+            }
+            None => {}
+        }
+
+        for partition in self
+            .partitions
+            .drain(mark.partitions.min(self.partitions.len())..)
+        {
+            self.size -= partition.len();
+        }
+        self.freed.split_off(&mark.partitions);
+        self.read_only.split_off(&mark.partitions);
+        self.capacity = mark.capacity;
     }
 
     // This was requested twice because the first time chatgpt didn't think about resizing partition
-    pub fn edit(&mut self, bytes: Vec<u8>, id: usize) {
-        if bytes.len() == self.get(id).len() {
-            let range = self.partitions[id].clone();
-            self.data.splice(range, bytes);
-            return;
+    pub fn edit(&mut self, bytes: Vec<u8>, id: usize) -> Result<(), HeapError> {
+        let old_len = match self.get_slice(id) {
+            Some(partition) => partition.len(),
+            None => return Err(HeapError::UnknownPartition(id)),
+        };
+        if bytes.len() == old_len {
+            self.partitions[id].copy_from_slice(&bytes);
+            return Ok(());
         }
 
-        self.delete(self.partitions.get(id).unwrap().clone());
-        let new_id = self.add(bytes);
-        self.partitions[id] = self.partitions[new_id].clone();
-        self.partitions.remove(new_id);
+        // the old bytes are given back before the new ones are stored
+        self.make_room(bytes.len().saturating_sub(old_len))?;
+        self.size = self.size - old_len + bytes.len();
+        self.partitions[id] = bytes;
+        Ok(())
     }
 
-    pub fn get(&self, id: usize) -> Vec<u8> {
-        let range = self.partitions[id].clone();
-        self.data[range].to_vec()
+    pub fn get(&self, id: usize) -> Option<Vec<u8>> {
+        self.get_slice(id).map(<[u8]>::to_vec)
     }
 
-    pub fn get_slice(&self, id: usize) -> &[u8] {
-        let range = self.partitions[id].clone();
-        &self.data[range]
+    pub fn get_slice(&self, id: usize) -> Option<&[u8]> {
+        if self.freed.contains(&id) {
+            return None;
+        }
+        self.partitions.get(id).map(Vec::as_slice)
     }
 
-    pub fn alloc(&mut self, additional: usize) {
-        self.data
-            .reserve_exact(additional + self.data.capacity() - self.data.len());
+    pub fn get_slice_mut(&mut self, id: usize) -> Option<&mut [u8]> {
+        if self.freed.contains(&id) {
+            return None;
+        }
+        self.partitions.get_mut(id).map(Vec::as_mut_slice)
+    }
+
+    pub fn alloc(&mut self, additional: usize) -> Result<(), HeapFull> {
+        let capacity = self.capacity.saturating_add(additional);
+        if let Some(max_size) = self.limit.max_size {
+            if capacity > max_size {
+                return Err(HeapFull {
+                    requested: additional,
+                    available: max_size.saturating_sub(self.capacity),
+                });
+            }
+        }
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    // Bytes that can still be stored without another ALOC or growing
+    pub fn free_space(&self) -> usize {
+        self.capacity - self.size
     }
 
     pub fn len(&self) -> usize {
-        self.data.capacity()
+        self.capacity
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     pub fn header(&self) -> Vec<u8> {
         let mut wtr = Vec::new();
 
-        wtr.write_u32::<LittleEndian>(self.partitions.len() as u32 * 4)
-            .unwrap();
-        wtr.write_u32::<LittleEndian>(self.data.len() as u32)
-            .unwrap();
-        wtr.write_u32::<LittleEndian>(self.data.capacity() as u32)
+        wtr.write_u32::<LittleEndian>((self.partitions.len() * PARTITION_ENTRY_SIZE) as u32)
             .unwrap();
+        wtr.write_u32::<LittleEndian>(self.size as u32).unwrap();
+        wtr.write_u32::<LittleEndian>(self.capacity as u32).unwrap();
 
         wtr
     }
@@ -97,70 +367,74 @@ impl MemoryHeap {
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut wtr = Vec::new();
 
-        for range in &self.partitions {
-            // wtr.write_u32::<LittleEndian>(range.start as u32).unwrap(); // needed if partitions move
-            wtr.write_u32::<LittleEndian>(range.end as u32).unwrap();
+        let mut end = 0;
+        for (id, partition) in self.partitions.iter().enumerate() {
+            end += partition.len();
+            wtr.write_u32::<LittleEndian>(end as u32).unwrap();
+            wtr.write_u8(if self.is_read_only(id) { READ_ONLY } else { 0 })
+                .unwrap();
         }
 
-        wtr.append(&mut self.data.clone());
+        for partition in &self.partitions {
+            wtr.extend_from_slice(partition);
+        }
 
         wtr
     }
 
-    pub fn from_bytes(bytes: &mut Cursor<&[u8]>, header: &mut Cursor<&[u8]>) -> Self {
-        let num_partitions = header.read_u32::<LittleEndian>().unwrap() as usize / 4;
-        let data_size = header.read_u32::<LittleEndian>().unwrap() as usize;
-        let capacity = header.read_u32::<LittleEndian>().unwrap() as usize;
+    pub fn from_bytes(bytes: &mut Cursor<&[u8]>, header: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let num_partitions = header.read_u32::<LittleEndian>()? as usize / PARTITION_ENTRY_SIZE;
+        let data_size = header.read_u32::<LittleEndian>()? as usize;
+        let capacity = header.read_u32::<LittleEndian>()? as usize;
 
-        let mut partitions = Vec::new();
+        let mut ends = Vec::new();
+        let mut read_only = BTreeSet::new();
         let mut current_start = 0;
-        while partitions.len() < num_partitions {
-            let next_start = bytes.read_u32::<LittleEndian>().unwrap() as usize;
-            partitions.push(current_start..next_start);
+        while ends.len() < num_partitions {
+            let next_start = bytes.read_u32::<LittleEndian>()? as usize;
+            if bytes.read_u8()? & READ_ONLY != 0 {
+                read_only.insert(ends.len());
+            }
+            if next_start < current_start || next_start > data_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Memory heap partition is out of bounds",
+                ));
+            }
+            ends.push(next_start);
             current_start = next_start;
         }
 
         let mut data: Vec<u8> = vec![0; data_size];
-        bytes.read_exact(&mut data).unwrap();
-
-        let mut sized: Vec<u8> = Vec::with_capacity(capacity);
-        data.clone_into(&mut sized);
-
-        Self {
-            data: sized,
+        bytes.read_exact(&mut data)?;
+
+        let mut start = 0;
+        let partitions = ends
+            .into_iter()
+            .map(|end| {
+                let partition = data[start..end].to_vec();
+                start = end;
+                partition
+            })
+            .collect();
+
+        Ok(Self {
             partitions,
-        }
+            size: current_start,
+            freed: BTreeSet::new(),
+            read_only,
+            capacity: capacity.max(data_size),
+            limit: HeapLimit::default(),
+        })
     }
 }
 
 impl IntoIterator for MemoryHeap {
     type Item = Vec<u8>;
-    type IntoIter = MemoryHeapIntoIterator;
+    type IntoIter = std::vec::IntoIter<Vec<u8>>;
 
     fn into_iter(self) -> Self::IntoIter {
-        MemoryHeapIntoIterator {
-            data: self.data.clone(),
-            partitions: self.partitions.clone(),
-            index: 0,
-        }
-    }
-}
-
-pub struct MemoryHeapIntoIterator {
-    data: Vec<u8>,
-    partitions: Vec<Range<usize>>,
-    index: usize,
-}
-
-impl Iterator for MemoryHeapIntoIterator {
-    type Item = Vec<u8>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let index = self.index;
-        self.index += 1;
-        self.partitions
-            .get(index)
-            .map(|range| self.data[range.clone()].to_vec())
+        self.partitions.into_iter()
     }
 }
 
@@ -173,11 +447,15 @@ mod tests {
     fn test_get() {
         let bytes = vec![1, 2, 3];
         let memory_heap = MemoryHeap {
-            data: bytes.clone(),
-            partitions: vec![0..3],
+            partitions: vec![bytes.clone()],
+            size: 3,
+            freed: BTreeSet::new(),
+            read_only: BTreeSet::new(),
+            capacity: 3,
+            limit: HeapLimit::default(),
         };
 
-        assert_eq!(memory_heap.get(0), bytes);
+        assert_eq!(memory_heap.get(0), Some(bytes));
     }
 
     #[test]
@@ -185,18 +463,8 @@ mod tests {
         let mut memory_heap = MemoryHeap::new(3);
 
         let bytes = vec![1, 2, 3];
-        let id = memory_heap.add(bytes.clone());
-        assert_eq!(memory_heap.get(id), bytes);
-    }
-
-    #[test]
-    fn test_remove() {
-        let mut memory_heap = MemoryHeap::new(3);
-
-        let bytes = vec![1, 2, 3];
-        let id = memory_heap.add(bytes.clone());
-        memory_heap.remove(id);
-        assert_eq!(memory_heap.data, Vec::<u8>::new());
+        let id = memory_heap.add(bytes.clone()).unwrap();
+        assert_eq!(memory_heap.get(id), Some(bytes));
     }
 
     #[test]
@@ -204,11 +472,11 @@ mod tests {
         let mut memory_heap = MemoryHeap::new(3);
 
         let bytes = vec![1, 2, 3];
-        let id = memory_heap.add(bytes.clone());
+        let id = memory_heap.add(bytes.clone()).unwrap();
         let new_bytes = vec![4, 5, 6];
-        memory_heap.edit(new_bytes.clone(), id);
+        memory_heap.edit(new_bytes.clone(), id).unwrap();
         println!("{memory_heap:?}");
-        assert_eq!(memory_heap.get(id), new_bytes);
+        assert_eq!(memory_heap.get(id), Some(new_bytes));
     }
 
     #[test]
@@ -216,11 +484,11 @@ mod tests {
         let mut memory_heap = MemoryHeap::new(4);
 
         let bytes = vec![1, 2, 3];
-        let id = memory_heap.add(bytes.clone());
+        let id = memory_heap.add(bytes.clone()).unwrap();
         let new_bytes = vec![4, 5, 6, 7];
-        memory_heap.edit(new_bytes.clone(), id);
+        memory_heap.edit(new_bytes.clone(), id).unwrap();
         println!("{memory_heap:?}");
-        assert_eq!(memory_heap.get(id), new_bytes);
+        assert_eq!(memory_heap.get(id), Some(new_bytes));
     }
 
     #[test]
@@ -228,37 +496,195 @@ mod tests {
         let mut memory_heap = MemoryHeap::new(3);
 
         let bytes = vec![1, 2, 3];
-        let id = memory_heap.add(bytes.clone());
+        let id = memory_heap.add(bytes.clone()).unwrap();
         let new_bytes = vec![4, 5];
-        memory_heap.edit(new_bytes.clone(), id);
+        memory_heap.edit(new_bytes.clone(), id).unwrap();
         println!("{memory_heap:?}");
-        assert_eq!(memory_heap.get(id), new_bytes);
+        assert_eq!(memory_heap.get(id), Some(new_bytes));
+    }
+
+    #[test]
+    fn test_free_and_insert() {
+        let mut memory_heap = MemoryHeap::new(8);
+
+        let first = memory_heap.add(vec![1, 2]).unwrap();
+        let second = memory_heap.add(vec![3, 4, 5]).unwrap();
+        assert!(memory_heap.free(first));
+        assert!(!memory_heap.free(first));
+        assert_eq!(memory_heap.get_slice(first), None);
+        assert_eq!(memory_heap.get(second), Some(vec![3, 4, 5]));
+        assert_eq!(memory_heap.free_space(), 5);
+
+        assert_eq!(memory_heap.insert(vec![6]), Ok(first));
+        assert_eq!(memory_heap.get(first), Some(vec![6]));
+        assert_eq!(memory_heap.insert(vec![7]), Ok(2));
+        assert_eq!(memory_heap.freed().count(), 0);
+    }
+
+    #[test]
+    fn test_edit_moved_partitions_to_bytes() {
+        let mut memory_heap = MemoryHeap::new(8);
+
+        memory_heap.add(vec![1, 2]).unwrap();
+        memory_heap.add(vec![3]).unwrap();
+        memory_heap.edit(vec![4, 5, 6], 0).unwrap();
+        assert_eq!(memory_heap.get(1), Some(vec![3]));
+        assert_eq!(
+            memory_heap.to_bytes(),
+            vec![3, 0, 0, 0, 0, 4, 0, 0, 0, 0, 4, 5, 6, 3]
+        );
+    }
+
+    #[test]
+    fn test_unknown_partition() {
+        let mut memory_heap = MemoryHeap::new(4);
+
+        let id = memory_heap.add(vec![1, 2]).unwrap();
+        assert_eq!(memory_heap.get(1), None);
+        assert_eq!(
+            memory_heap.edit(vec![3], 1),
+            Err(HeapError::UnknownPartition(1))
+        );
+        memory_heap.free(id);
+        assert_eq!(memory_heap.get(id), None);
+        assert_eq!(
+            memory_heap.edit(vec![3], id),
+            Err(HeapError::UnknownPartition(id))
+        );
+        assert_eq!(memory_heap.free_space(), 4);
+    }
+
+    #[test]
+    fn test_mark_and_restore() {
+        let mut memory_heap = MemoryHeap::new(4);
+        memory_heap.set_limit(HeapLimit {
+            max_size: None,
+            grow: true,
+        });
+        memory_heap.add(vec![1, 2]).unwrap();
+        memory_heap.add(vec![3]).unwrap();
+        let original = memory_heap.clone();
+
+        let mark = memory_heap.mark_partition(0);
+        memory_heap.edit(vec![4, 5, 6, 7], 0).unwrap();
+        assert_eq!(memory_heap.len(), 5);
+        memory_heap.restore(mark);
+        assert_eq!(memory_heap, original);
+
+        let mark = memory_heap.mark_range(0, 1..2);
+        memory_heap.get_slice_mut(0).unwrap()[1] = 9;
+        memory_heap.restore(mark);
+        assert_eq!(memory_heap, original);
+
+        let mark = memory_heap.mark_partition(1);
+        memory_heap.free(1);
+        let freed = memory_heap.clone();
+        memory_heap.restore(mark);
+        assert_eq!(memory_heap, original);
+
+        memory_heap = freed;
+        for _ in 0..2 {
+            let mark = memory_heap.mark_partition(memory_heap.next_id());
+            let before = memory_heap.clone();
+            memory_heap.insert(vec![8, 8]).unwrap();
+            memory_heap.restore(mark);
+            assert_eq!(memory_heap, before);
+            memory_heap.insert(vec![8, 8]).unwrap();
+        }
+        assert_eq!(memory_heap.next_id(), 3);
     }
 
     #[test]
     fn test_alloc() {
         let mut memory_heap = MemoryHeap::new(0);
 
-        memory_heap.alloc(1024);
+        memory_heap.alloc(1024).unwrap();
         assert_eq!(memory_heap.len(), 1024);
     }
 
+    #[test]
+    fn test_heap_full() {
+        let mut memory_heap = MemoryHeap::new(3);
+
+        let id = memory_heap.add(vec![1, 2]).unwrap();
+        assert_eq!(
+            memory_heap.add(vec![3, 4]),
+            Err(HeapFull {
+                requested: 2,
+                available: 1
+            })
+        );
+        assert_eq!(
+            memory_heap.edit(vec![5, 6, 7, 8], id),
+            Err(HeapError::Full(HeapFull {
+                requested: 2,
+                available: 1
+            }))
+        );
+        assert_eq!(memory_heap.get(id), Some(vec![1, 2]));
+        memory_heap.edit(vec![5, 6, 7], id).unwrap();
+        assert_eq!(memory_heap.get(id), Some(vec![5, 6, 7]));
+    }
+
+    #[test]
+    fn test_grow_up_to_limit() {
+        let mut memory_heap = MemoryHeap::new(2);
+        memory_heap.set_limit(HeapLimit {
+            max_size: Some(4),
+            grow: true,
+        });
+
+        let id = memory_heap.add(vec![1, 2, 3]).unwrap();
+        assert_eq!(memory_heap.len(), 3);
+        memory_heap.edit(vec![4, 5, 6, 7], id).unwrap();
+        assert_eq!(memory_heap.len(), 4);
+        assert_eq!(
+            memory_heap.add(vec![8]),
+            Err(HeapFull {
+                requested: 1,
+                available: 0
+            })
+        );
+        assert!(memory_heap.alloc(1).is_err());
+    }
+
+    #[test]
+    fn test_limit_gives_back_reserved_room() {
+        let mut memory_heap = MemoryHeap::new(256);
+        memory_heap.add(vec![1, 2, 3]).unwrap();
+        memory_heap.set_limit(HeapLimit {
+            max_size: Some(2),
+            grow: false,
+        });
+        assert_eq!(memory_heap.len(), 3);
+        assert_eq!(memory_heap.get(0), Some(vec![1, 2, 3]));
+    }
+
     #[test]
     fn to_bytes() {
         let memory_heap = MemoryHeap {
-            data: vec![102, 18, 12, 152, 230, 56, 8, 2, 54, 0, 0, 1, 32],
-            partitions: vec![0..2, 2..5, 5..8, 8..13],
+            partitions: vec![
+                vec![102, 18],
+                vec![12, 152, 230],
+                vec![56, 8, 2],
+                vec![54, 0, 0, 1, 32],
+            ],
+            size: 13,
+            freed: BTreeSet::new(),
+            read_only: BTreeSet::from([1]),
+            capacity: 13,
+            limit: HeapLimit::default(),
         };
 
         assert_eq!(
             memory_heap.header(),
-            vec![16, 0, 0, 0, 13, 0, 0, 0, 13, 0, 0, 0]
+            vec![20, 0, 0, 0, 13, 0, 0, 0, 13, 0, 0, 0]
         );
         assert_eq!(
             memory_heap.to_bytes(),
             vec![
-                2, 0, 0, 0, 5, 0, 0, 0, 8, 0, 0, 0, 13, 0, 0, 0, 102, 18, 12, 152, 230, 56, 8, 2,
-                54, 0, 0, 1, 32
+                2, 0, 0, 0, 0, 5, 0, 0, 0, 1, 8, 0, 0, 0, 0, 13, 0, 0, 0, 0, 102, 18, 12, 152, 230,
+                56, 8, 2, 54, 0, 0, 1, 32
             ]
         );
     }
@@ -266,16 +692,40 @@ mod tests {
     #[test]
     fn from_bytes() {
         let memory_heap = MemoryHeap {
-            data: vec![102, 18, 12, 152, 230, 56, 8, 2, 54, 0, 0, 1, 32],
-            partitions: vec![0..2, 2..5, 5..8, 8..13],
+            partitions: vec![
+                vec![102, 18],
+                vec![12, 152, 230],
+                vec![56, 8, 2],
+                vec![54, 0, 0, 1, 32],
+            ],
+            size: 13,
+            freed: BTreeSet::new(),
+            read_only: BTreeSet::from([1]),
+            capacity: 13,
+            limit: HeapLimit::default(),
         };
 
         let header = memory_heap.header();
         let bytes = memory_heap.to_bytes();
         let new_memory_heap =
-            MemoryHeap::from_bytes(&mut Cursor::new(&bytes[..]), &mut Cursor::new(&header[..]));
+            MemoryHeap::from_bytes(&mut Cursor::new(&bytes[..]), &mut Cursor::new(&header[..]))
+                .unwrap();
 
         assert_eq!(new_memory_heap, memory_heap);
-        assert_eq!(new_memory_heap.data.capacity(), memory_heap.data.capacity());
+        assert_eq!(new_memory_heap.len(), memory_heap.len());
+    }
+
+    #[test]
+    fn from_bytes_out_of_bounds() {
+        let header = vec![10, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0];
+        let bytes = vec![2, 0, 0, 0, 0, 9, 0, 0, 0, 0, 1, 2];
+
+        let result =
+            MemoryHeap::from_bytes(&mut Cursor::new(&bytes[..]), &mut Cursor::new(&header[..]));
+        assert!(result.is_err());
+
+        let result =
+            MemoryHeap::from_bytes(&mut Cursor::new(&bytes[..5]), &mut Cursor::new(&header[..]));
+        assert!(result.is_err());
     }
 }
@@ -1,96 +1,377 @@
 use crate::{
     assembler::{PIE_HEADER_LENGTH, PIE_HEADER_PREFIX},
+    instruction::{Opcode, Operand},
     vm::cursor::ProgramCursor,
 };
 use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::Cursor;
+use std::{collections::BTreeSet, io::Cursor, ops::Range, time::Duration};
 use uuid::Uuid;
 
 use self::{
+    arithmetic::ArithmeticMode,
+    budget::Budget,
+    clock::Clock,
+    error::VMError,
     events::{VMEvent, VMEventType},
-    memory::MemoryHeap,
-    operator::Operator,
+    history::History,
+    io::{StdIo, VMIo},
+    memory::{HeapError, HeapFull, MemoryHeap},
+    profiler::Profile,
+    trace::Tracer,
 };
 
+pub mod arithmetic;
+pub mod budget;
+pub mod clock;
 pub mod cursor;
+pub mod debugger;
+pub mod error;
 pub mod events;
+pub mod history;
+pub mod io;
 pub mod memory;
 pub mod operator;
+pub mod profiler;
+pub mod snapshot;
 #[cfg(test)]
 pub mod tests;
+pub mod trace;
+
+// Maximum depth of both the value stack and the return address stack
+pub const STACK_SIZE: usize = 1024;
 
 #[derive(Debug, Clone)]
 pub struct VM {
     pub registers: [i32; 32],
+    pub float_registers: [f64; 32],
     pub program: Vec<u8>,
     pub program_cursor: Cursor<Vec<u8>>,
     pub logical_cores: usize,
     pub memory_heap: MemoryHeap,
-    remainder: u32,
+    pub io: Box<dyn VMIo>,
+    pub budget: Budget,
+    pub arithmetic_mode: ArithmeticMode,
+    pub clock: Clock,
+    pub tracer: Option<Tracer>,
+    pub profile: Option<Profile>,
+    remainder: i32,
+    overflow_flag: bool,
     equal_flag: bool,
+    stack: Vec<i32>,
+    return_addresses: Vec<usize>,
     id: Uuid,
     events: Vec<VMEvent>,
+    current_pc: usize,
+    current_opcode: Opcode,
+    breakpoints: BTreeSet<usize>,
+    paused_at: Option<usize>,
+    history: History,
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VM {
     pub fn new() -> Self {
         Self {
             registers: [0; 32],
+            float_registers: [0.0; 32],
             remainder: 0,
+            overflow_flag: false,
+            arithmetic_mode: ArithmeticMode::default(),
+            clock: Clock::default(),
+            tracer: None,
+            profile: None,
             equal_flag: false,
+            stack: Vec::new(),
+            return_addresses: Vec::new(),
             program: Vec::new(),
             program_cursor: Cursor::new(Vec::new()),
             memory_heap: MemoryHeap::new(0),
+            io: Box::new(StdIo),
+            budget: Budget::unlimited(),
             events: Vec::new(),
             id: Uuid::new_v4(),
             logical_cores: num_cpus::get(),
+            current_pc: 0,
+            current_opcode: Opcode::IGL,
+            breakpoints: BTreeSet::new(),
+            paused_at: None,
+            history: History::default(),
         }
     }
 
-    // Loops as long as instructions can be executed.
+    // Loads the program header and loops as long as instructions can be executed,
+    // no breakpoint is reached and the budget allows it.
     pub fn run(&mut self) -> Vec<VMEvent> {
-        self.events
-            .push(VMEvent::now(VMEventType::Start, self.id.clone()));
+        if self.start().is_ok() {
+            self.continue_execution();
+        }
+        self.events.clone()
+    }
+
+    // Keeps executing from the current position, with a fresh budget.
+    // Used to continue a program that ran out of budget or hit a breakpoint.
+    pub fn resume(&mut self) -> Vec<VMEvent> {
+        self.continue_execution();
+        self.events.clone()
+    }
 
+    // Loads the program header, the program can then be stepped through or continued
+    pub fn start(&mut self) -> Result<(), VMError> {
+        self.push_event(VMEventType::Start);
+        self.paused_at = None;
+        self.clear_history();
+
+        if let Err(error) = self.load_header() {
+            self.crash(error.clone());
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn load_header(&mut self) -> Result<(), VMError> {
         if !self.verify_header() {
-            self.events
-                .push(VMEvent::now(VMEventType::Crash, self.id.clone()));
-            println!("Header was incorrect");
-            return self.events.clone();
+            return Err(VMError::InvalidHeader);
         }
 
-        self.memory_heap = MemoryHeap::from_bytes(
+        let heap = MemoryHeap::from_bytes(
             &mut Cursor::new(&self.program[PIE_HEADER_LENGTH..]),
             &mut Cursor::new(&self.program[PIE_HEADER_PREFIX.len()..PIE_HEADER_PREFIX.len() + 12]),
-        );
+        )
+        .map_err(|_| VMError::InvalidHeader)?;
+        self.replace_heap(heap);
         self.program_cursor
-            .set_position((PIE_HEADER_LENGTH + self.get_starting_offset()) as u64);
+            .set_position((PIE_HEADER_LENGTH + self.get_starting_offset()?) as u64);
+
+        Ok(())
+    }
+
+    fn crash(&mut self, error: VMError) {
+        let pc = error.pc();
+        self.push_event(VMEventType::Crash { error, pc });
+    }
+
+    fn push_event(&mut self, event: VMEventType) {
+        let event = VMEvent::now(event, self.id).at_virtual_time(self.clock.virtual_time());
+        self.events.push(event);
+    }
 
-        let mut is_done = None;
-        while is_done.is_none() {
-            is_done = self.execute_instruction();
+    // Time slept so far on a virtual clock, None on the real one
+    pub fn virtual_time(&self) -> Option<Duration> {
+        self.clock.virtual_time()
+    }
+
+    // Remainder of the last DIV or MOD
+    pub fn remainder(&self) -> i32 {
+        self.remainder
+    }
+
+    // Whether the last ADD, SUB, MUL, DIV, MOD, INC or DEC overflowed
+    pub fn overflow_flag(&self) -> bool {
+        self.overflow_flag
+    }
+
+    fn missing_operand(&self) -> VMError {
+        VMError::MissingOperand {
+            pc: self.current_pc,
+            opcode: self.current_opcode,
         }
+    }
+
+    // Reads a register operand and checks that it names one of the 32 registers
+    fn read_register(&mut self) -> Result<usize, VMError> {
+        let register = self
+            .program_cursor
+            .read_register_index()
+            .ok_or_else(|| self.missing_operand())?;
+        if register >= self.registers.len() {
+            return Err(VMError::InvalidRegister {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                register,
+            });
+        }
+        Ok(register)
+    }
+
+    fn read_register_value(&mut self) -> Result<i32, VMError> {
+        let register = self.read_register()?;
+        Ok(self.registers[register])
+    }
+
+    fn read_float_register_value(&mut self) -> Result<f64, VMError> {
+        let register = self.read_register()?;
+        Ok(self.float_registers[register])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, VMError> {
+        self.program_cursor
+            .next_16_bits()
+            .ok_or_else(|| self.missing_operand())
+    }
+
+    fn read_i32(&mut self) -> Result<i32, VMError> {
+        self.program_cursor
+            .next_32_bits()
+            .map(|value| value as i32)
+            .ok_or_else(|| self.missing_operand())
+    }
 
-        self.events.push(VMEvent::now(
-            VMEventType::GracefulStop {
-                code: is_done.unwrap(),
+    fn read_f64(&mut self) -> Result<f64, VMError> {
+        self.program_cursor
+            .next_64_bits()
+            .map(f64::from_bits)
+            .ok_or_else(|| self.missing_operand())
+    }
+
+    // Reads a heap index operand, or the id held by a register for the instructions
+    // reading handles, and checks that it points to an existing partition
+    fn read_heap_index(&mut self) -> Result<usize, VMError> {
+        if self.current_opcode.reads_handles() {
+            return self.read_handle();
+        }
+        let index = self
+            .program_cursor
+            .read_index()
+            .ok_or_else(|| self.missing_operand())?;
+        if self.memory_heap.get_slice(index).is_none() {
+            return Err(VMError::InvalidHeapIndex {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                index,
+            });
+        }
+        Ok(index)
+    }
+
+    fn check_writable(&self, index: usize) -> Result<(), VMError> {
+        if self.memory_heap.is_read_only(index) {
+            return Err(VMError::ReadOnlyPartition {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                index,
+            });
+        }
+        Ok(())
+    }
+
+    fn read_handle(&mut self) -> Result<usize, VMError> {
+        let handle = self.read_register_value()?;
+        match usize::try_from(handle) {
+            Ok(index) if self.memory_heap.get_slice(index).is_some() => Ok(index),
+            _ => Err(VMError::InvalidHandle {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                handle,
+            }),
+        }
+    }
+
+    // The partition a heap index operand names, or a handle register holds in `registers`
+    fn partition_operand(operand: Option<&Operand>, registers: &[i32; 32]) -> Option<usize> {
+        match operand? {
+            Operand::HeapIndex(index) => Some(*index as usize),
+            Operand::Register(register) => {
+                usize::try_from(*registers.get(*register as usize)?).ok()
+            }
+            _ => None,
+        }
+    }
+
+    fn io_error(&self, error: std::io::Error) -> VMError {
+        VMError::Io {
+            pc: self.current_pc,
+            opcode: self.current_opcode,
+            error: error.to_string(),
+        }
+    }
+
+    fn heap_full(&self, error: HeapFull) -> VMError {
+        VMError::HeapFull {
+            pc: self.current_pc,
+            opcode: self.current_opcode,
+            requested: error.requested,
+            available: error.available,
+        }
+    }
+
+    fn heap_error(&self, error: HeapError) -> VMError {
+        match error {
+            HeapError::Full(error) => self.heap_full(error),
+            HeapError::UnknownPartition(index) => VMError::InvalidHeapIndex {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                index,
             },
-            self.id.clone(),
-        ));
+        }
+    }
 
-        self.events.clone()
+    // A new heap keeps the limit the VM was given
+    pub(super) fn replace_heap(&mut self, mut heap: MemoryHeap) {
+        heap.set_limit(self.memory_heap.limit());
+        self.memory_heap = heap;
     }
 
-    // Executes one instruction. Meant to allow for more controlled execution of the VM
-    pub fn run_once(&mut self) {
-        self.execute_instruction();
+    fn write(&mut self, text: &str) -> Result<(), VMError> {
+        self.io.write(text).map_err(|e| self.io_error(e))
     }
 
-    fn read_data(&mut self) -> Option<&str> {
-        if let Some(index) = self.program_cursor.next_16_bits() {
-            std::str::from_utf8(&self.memory_heap.get_slice(index as usize)).ok()
-        } else {
-            None
+    // Reads a heap index operand pointing to a partition holding exactly one i32
+    fn read_int_partition(&mut self) -> Result<usize, VMError> {
+        self.read_sized_partition(4)
+    }
+
+    // Reads a heap index operand pointing to a partition holding exactly one f64
+    fn read_float_partition(&mut self) -> Result<usize, VMError> {
+        self.read_sized_partition(8)
+    }
+
+    fn read_sized_partition(&mut self, expected: usize) -> Result<usize, VMError> {
+        let index = self.read_heap_index()?;
+        let size = self.memory_heap.get_slice(index).unwrap_or_default().len();
+        if size != expected {
+            return Err(VMError::WrongPartitionSize {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                index,
+                size,
+                expected,
+            });
+        }
+        Ok(index)
+    }
+
+    // Reads a handle and an offset register, checks that `width` bytes fit there
+    fn read_heap_access(&mut self, width: usize) -> Result<(usize, Range<usize>), VMError> {
+        let index = self.read_heap_index()?;
+        let offset = self.read_register_value()?;
+        let size = self.memory_heap.get_slice(index).unwrap_or_default().len();
+        match usize::try_from(offset) {
+            Ok(start) if start + width <= size => Ok((index, start..start + width)),
+            _ => Err(VMError::OutOfBounds {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                index,
+                offset,
+                size,
+            }),
+        }
+    }
+
+    fn read_data(&mut self) -> Result<String, VMError> {
+        let index = self.read_heap_index()?;
+        let bytes = self.memory_heap.get_slice(index).unwrap_or_default();
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(s.to_owned()),
+            Err(_) => Err(VMError::InvalidUtf8 {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                index,
+            }),
         }
     }
 
@@ -111,31 +392,41 @@ impl VM {
     }
 
     pub fn set_program(&mut self, prog: Vec<u8>, mem: MemoryHeap) {
-        self.memory_heap = mem.clone();
-        self.program = Self::prepend_header(prog, mem);
+        self.program = Self::prepend_header(prog, mem.clone());
+        self.replace_heap(mem);
         self.update_program_cursor();
-        self.program_cursor
-            .set_position((PIE_HEADER_LENGTH + self.get_starting_offset()) as u64);
+        self.clear_history();
+        if let Ok(offset) = self.get_starting_offset() {
+            self.program_cursor
+                .set_position((PIE_HEADER_LENGTH + offset) as u64);
+        }
     }
 
     fn verify_header(&self) -> bool {
-        if self.program[0..PIE_HEADER_PREFIX.len()] != PIE_HEADER_PREFIX {
+        if self.program.len() < PIE_HEADER_LENGTH
+            || self.program[0..PIE_HEADER_PREFIX.len()] != PIE_HEADER_PREFIX
+        {
             return false;
         }
         true
     }
 
-    fn get_starting_offset(&self) -> usize {
+    // Size of the partition table and data between the header and the code
+    fn get_starting_offset(&self) -> Result<usize, VMError> {
+        if !self.verify_header() {
+            return Err(VMError::InvalidHeader);
+        }
         let mut rdr =
             Cursor::new(&self.program[PIE_HEADER_PREFIX.len()..PIE_HEADER_PREFIX.len() + 16]);
-        rdr.read_u32::<LittleEndian>().unwrap() as usize
-            + rdr.read_u32::<LittleEndian>().unwrap() as usize
+        let table_size = rdr.read_u32::<LittleEndian>().unwrap() as usize;
+        let data_size = rdr.read_u32::<LittleEndian>().unwrap() as usize;
+        Ok(table_size + data_size)
     }
 
     pub fn prepend_header(mut b: Vec<u8>, mem: MemoryHeap) -> Vec<u8> {
         let mut prepension = vec![];
         for byte in PIE_HEADER_PREFIX.into_iter() {
-            prepension.push(byte.clone());
+            prepension.push(byte);
         }
 
         prepension.append(&mut mem.header());
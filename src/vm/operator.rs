@@ -1,227 +1,502 @@
-use std::{
-    fmt::Debug,
-    io::{self, stdout, Write},
-    str::FromStr,
-    thread,
-    time::Duration,
-};
+use std::{fmt::Debug, str::FromStr, time::Duration};
+
+use byteorder::{ByteOrder, LittleEndian};
 
-use crate::{assembler::PIE_HEADER_LENGTH, instruction::Opcode};
+use crate::{
+    assembler::PIE_HEADER_LENGTH,
+    instruction::{Opcode, OperandKind},
+};
 
-use super::{cursor::ProgramCursor, VM};
+use super::{arithmetic::Outcome, cursor::ProgramCursor, error::VMError, STACK_SIZE, VM};
 
 pub trait Operator {
-    fn execute_instruction(&mut self) -> Option<u32>;
-    fn calculate<F: FnOnce(i32, i32) -> (i32, Option<u32>)>(&mut self, op: F);
-    fn compare<F: FnOnce(i32, i32) -> bool>(&mut self, comparator: F);
-    fn sleep(&mut self, unit: i32);
-    fn print(&mut self);
-    fn jump<F: FnOnce(usize, usize, usize, bool) -> usize>(&mut self, jump: F);
-    fn alloc(&mut self);
-    fn load(&mut self);
-    fn ask<T>(&mut self) -> Option<T>
+    fn execute_instruction(&mut self) -> Result<Option<u32>, VMError>;
+    fn calculate<F: FnOnce(i32, i32) -> i32>(&mut self, op: F) -> Result<(), VMError>;
+    fn calculate_checked<F: FnOnce(i32, i32) -> Option<Outcome>>(
+        &mut self,
+        op: F,
+    ) -> Result<(), VMError>;
+    fn increment<F: FnOnce(i32) -> Outcome>(&mut self, op: F) -> Result<(), VMError>;
+    fn settle(&mut self, outcome: Outcome) -> Result<i32, VMError>;
+    fn compare<F: FnOnce(i32, i32) -> bool>(&mut self, comparator: F) -> Result<(), VMError>;
+    fn calculate_float<F: FnOnce(f64, f64) -> f64>(&mut self, op: F) -> Result<(), VMError>;
+    fn compare_float<F: FnOnce(f64, f64) -> bool>(&mut self, comparator: F) -> Result<(), VMError>;
+    fn sleep(&mut self, unit: i64) -> Result<(), VMError>;
+    fn print(&mut self) -> Result<(), VMError>;
+    fn jump<F: FnOnce(i64, i64, i64, bool) -> i64>(&mut self, jump: F) -> Result<(), VMError>;
+    fn jump_to(&mut self, target: i64) -> Result<(), VMError>;
+    fn read_target(&mut self) -> Result<i64, VMError>;
+    fn push(&mut self) -> Result<(), VMError>;
+    fn pop(&mut self) -> Result<(), VMError>;
+    fn call(&mut self) -> Result<(), VMError>;
+    fn ret(&mut self) -> Result<(), VMError>;
+    fn alloc(&mut self) -> Result<(), VMError>;
+    fn new_partition(&mut self) -> Result<(), VMError>;
+    fn load(&mut self) -> Result<(), VMError>;
+    fn load_wide(&mut self) -> Result<(), VMError>;
+    fn load_float(&mut self) -> Result<(), VMError>;
+    fn ask<T>(&mut self, prompt: &str) -> Result<Option<T>, VMError>
     where
         T: FromStr,
         T::Err: Debug;
 }
 
 impl Operator for VM {
-    fn execute_instruction(&mut self) -> Option<u32> {
-        let opcode = self.program_cursor.read_opcode();
-        if opcode.is_none() {
-            return Some(1);
-        }
+    fn execute_instruction(&mut self) -> Result<Option<u32>, VMError> {
+        self.current_pc = self.program_cursor.position() as usize;
+        let byte = match self.program_cursor.next_8_bits() {
+            Some(byte) => byte,
+            None => return Ok(Some(1)),
+        };
 
-        let opcode = opcode.unwrap();
+        let opcode = Opcode::from(byte);
+        self.current_opcode = opcode;
+        let remaining = (self.program_cursor.get_ref().len() as u64)
+            .saturating_sub(self.program_cursor.position());
+        if remaining < opcode.operands_size() as u64 {
+            return Err(self.missing_operand());
+        }
         match opcode {
-            Opcode::HLT => {
-                println!("HLT encountered");
-                return Some(0);
-            }
-            Opcode::LOAD => self.load(),
-            Opcode::ADD => self.calculate(|a, b| (a + b, None)),
-            Opcode::SUB => self.calculate(|a, b| (a - b, None)),
-            Opcode::MUL => self.calculate(|a, b| (a * b, None)),
-            Opcode::DIV => self.calculate(|a, b| (a / b, Some((a % b) as u32))),
-            Opcode::JMP => self.jump(|target, offset, _, _| PIE_HEADER_LENGTH + target + offset),
-            Opcode::JMPF => self.jump(|target, _, current, _| current + target),
-            Opcode::JMPB => self.jump(|target, _, current, _| current - target),
-            Opcode::JEQ => self.jump(|target, offset, current, equal_flag| {
+            Opcode::HLT => return Ok(Some(0)),
+            Opcode::LOAD => self.load()?,
+            Opcode::LOADW => self.load_wide()?,
+            Opcode::ADD => self.calculate_checked(|a, b| {
+                Some(Outcome::new(a.overflowing_add(b), a.saturating_add(b)))
+            })?,
+            Opcode::SUB => self.calculate_checked(|a, b| {
+                Some(Outcome::new(a.overflowing_sub(b), a.saturating_sub(b)))
+            })?,
+            Opcode::MUL => self.calculate_checked(|a, b| {
+                Some(Outcome::new(a.overflowing_mul(b), a.saturating_mul(b)))
+            })?,
+            // i32::MIN / -1 is the only division that overflows
+            Opcode::DIV => self.calculate_checked(|a, b| {
+                (b != 0).then(|| {
+                    Outcome::new(a.overflowing_div(b), a.saturating_div(b))
+                        .with_remainder(a.wrapping_rem(b))
+                })
+            })?,
+            Opcode::MOD => self.calculate_checked(|a, b| {
+                (b != 0)
+                    .then(|| Outcome::exact(a.wrapping_rem(b)).with_remainder(a.wrapping_rem(b)))
+            })?,
+            Opcode::REM => {
+                let register = self.read_register()?;
+                self.registers[register] = self.remainder;
+            }
+            Opcode::INC => self.increment(|value| {
+                Outcome::new(value.overflowing_add(1), value.saturating_add(1))
+            })?,
+            Opcode::DEC => self.increment(|value| {
+                Outcome::new(value.overflowing_sub(1), value.saturating_sub(1))
+            })?,
+            Opcode::AND => self.calculate(|a, b| a & b)?,
+            Opcode::OR => self.calculate(|a, b| a | b)?,
+            Opcode::XOR => self.calculate(|a, b| a ^ b)?,
+            Opcode::NOT => {
+                let value = self.read_register_value()?;
+                let destination = self.read_register()?;
+                self.registers[destination] = !value;
+            }
+            // Shift amounts only keep their lowest 5 bits, like the hardware does
+            Opcode::SHL => self.calculate(|a, b| a.wrapping_shl(b as u32))?,
+            Opcode::SHR => self.calculate(|a, b| (a as u32).wrapping_shr(b as u32) as i32)?,
+            Opcode::SAR => self.calculate(|a, b| a.wrapping_shr(b as u32))?,
+            Opcode::JMP | Opcode::JMPT => {
+                self.jump(|target, offset, _, _| PIE_HEADER_LENGTH as i64 + target + offset)?
+            }
+            Opcode::JMPF | Opcode::JMPFT => self.jump(|target, _, current, _| current + target)?,
+            Opcode::JMPB | Opcode::JMPBT => self.jump(|target, _, current, _| current - target)?,
+            Opcode::JEQ | Opcode::JEQT => self.jump(|target, offset, current, equal_flag| {
                 if equal_flag {
-                    PIE_HEADER_LENGTH + target + offset
+                    PIE_HEADER_LENGTH as i64 + target + offset
                 } else {
                     current
                 }
-            }),
-            Opcode::EQ => self.compare(|a, b| a == b),
-            Opcode::NEQ => self.compare(|a, b| a != b),
-            Opcode::GT => self.compare(|a, b| a > b),
-            Opcode::LT => self.compare(|a, b| a < b),
-            Opcode::GTQ => self.compare(|a, b| a >= b),
-            Opcode::LTQ => self.compare(|a, b| a <= b),
-            Opcode::ALOC => self.alloc(),
-            Opcode::PRTS => self.print(),
+            })?,
+            Opcode::JNEQ | Opcode::JNEQT => self.jump(|target, offset, current, equal_flag| {
+                if equal_flag {
+                    current
+                } else {
+                    PIE_HEADER_LENGTH as i64 + target + offset
+                }
+            })?,
+            Opcode::EQ => self.compare(|a, b| a == b)?,
+            Opcode::NEQ => self.compare(|a, b| a != b)?,
+            Opcode::GT => self.compare(|a, b| a > b)?,
+            Opcode::LT => self.compare(|a, b| a < b)?,
+            Opcode::GTQ => self.compare(|a, b| a >= b)?,
+            Opcode::LTQ => self.compare(|a, b| a <= b)?,
+            Opcode::LOADF => self.load_float()?,
+            Opcode::ADDF => self.calculate_float(|a, b| a + b)?,
+            Opcode::SUBF => self.calculate_float(|a, b| a - b)?,
+            Opcode::MULF => self.calculate_float(|a, b| a * b)?,
+            Opcode::DIVF => self.calculate_float(|a, b| a / b)?,
+            Opcode::EQF => self.compare_float(|a, b| a == b)?,
+            Opcode::NEQF => self.compare_float(|a, b| a != b)?,
+            Opcode::GTF => self.compare_float(|a, b| a > b)?,
+            Opcode::LTF => self.compare_float(|a, b| a < b)?,
+            Opcode::GTQF => self.compare_float(|a, b| a >= b)?,
+            Opcode::LTQF => self.compare_float(|a, b| a <= b)?,
+            Opcode::ITOF => {
+                let value = self.read_register_value()?;
+                let destination = self.read_register()?;
+                self.float_registers[destination] = value as f64;
+            }
+            // Truncates towards zero, saturates at the i32 bounds and turns NaN into 0
+            Opcode::FTOI => {
+                let value = self.read_float_register_value()?;
+                let destination = self.read_register()?;
+                self.registers[destination] = value as i32;
+            }
+            Opcode::PRTF => {
+                let value = self.read_float_register_value()?;
+                self.write(&format!("{value}\n"))?;
+            }
+            Opcode::ALOC => self.alloc()?,
+            Opcode::PRTS | Opcode::PRTSR => self.print()?,
             Opcode::PRTI => {
-                let register = self.registers[self.program_cursor.read_register_index().unwrap()];
-                println!("{register}");
-            },
-            Opcode::SLP => self.sleep(1),
-            Opcode::SLPS => self.sleep(1000),
+                let register = self.read_register_value()?;
+                self.write(&format!("{register}\n"))?;
+            }
+            Opcode::SLP => self.sleep(1)?,
+            Opcode::SLPS => self.sleep(1000)?,
+            // The destination is checked before asking, a fault must not eat a line of input
             Opcode::ASKI => {
-                if let Some(integer) = self.ask::<i32>() {
-                    let index = self.program_cursor.read_register_index().unwrap();
-
+                let prompt = self.read_data()?;
+                let index = self.read_register()?;
+                if let Some(integer) = self.ask::<i32>(&prompt)? {
                     self.registers[index] = integer;
-                } else {
-                    self.program_cursor.next_16_bits();
                 }
             }
-            Opcode::ASKS => {
-                if let Some(string) = self.ask::<String>() {
-                    let index = self.program_cursor.read_index().unwrap();
-
-                    self.memory_heap.edit(string.as_bytes().to_vec(), index);
-                } else {
-                    self.program_cursor.next_16_bits();
+            Opcode::ASKS | Opcode::ASKSR => {
+                let prompt = self.read_data()?;
+                let index = self.read_heap_index()?;
+                self.check_writable(index)?;
+                if let Some(string) = self.ask::<String>(&prompt)? {
+                    self.memory_heap
+                        .edit(string.as_bytes().to_vec(), index)
+                        .map_err(|e| self.heap_error(e))?;
                 }
             }
-            Opcode::GRPS => {
-                let mut cursor = self.program_cursor.clone();
-                let left =
-                    std::str::from_utf8(&self.memory_heap.get_slice(cursor.read_index().unwrap()))
-                        .unwrap();
-                let right =
-                    std::str::from_utf8(&self.memory_heap.get_slice(cursor.read_index().unwrap()))
-                        .unwrap();
-                let id = cursor.read_index().unwrap();
-                self.program_cursor.set_position(cursor.position());
+            Opcode::GRPS | Opcode::GRPSR => {
+                let left = self.read_data()?;
+                let right = self.read_data()?;
+                let id = self.read_heap_index()?;
+                self.check_writable(id)?;
 
                 let combined = [left, right].join("");
 
                 self.memory_heap
-                    .edit(combined.as_bytes().to_vec(), id as usize);
+                    .edit(combined.as_bytes().to_vec(), id)
+                    .map_err(|e| self.heap_error(e))?;
             }
             Opcode::EQS => {
-                let mut cursor = self.program_cursor.clone();
-                let left =
-                    std::str::from_utf8(&self.memory_heap.get_slice(cursor.read_index().unwrap()))
-                        .unwrap();
-                let right =
-                    std::str::from_utf8(&self.memory_heap.get_slice(cursor.read_index().unwrap()))
-                        .unwrap();
-                self.program_cursor.set_position(cursor.position());
+                let left = self.read_data()?;
+                let right = self.read_data()?;
 
                 self.equal_flag = left == right
             }
             Opcode::NEQS => {
-                let mut cursor = self.program_cursor.clone();
-                let left =
-                    std::str::from_utf8(&self.memory_heap.get_slice(cursor.read_index().unwrap()))
-                        .unwrap();
-                let right =
-                    std::str::from_utf8(&self.memory_heap.get_slice(cursor.read_index().unwrap()))
-                        .unwrap();
-                self.program_cursor.set_position(cursor.position());
+                let left = self.read_data()?;
+                let right = self.read_data()?;
 
                 self.equal_flag = left != right
             }
+            Opcode::LDI => {
+                let index = self.read_int_partition()?;
+                let register = self.read_register()?;
+                let bytes = self.memory_heap.get_slice(index).unwrap_or_default();
+                self.registers[register] = LittleEndian::read_i32(bytes);
+            }
+            Opcode::STI => {
+                let value = self.read_register_value()?;
+                let index = self.read_int_partition()?;
+                self.check_writable(index)?;
+                self.memory_heap
+                    .edit(value.to_le_bytes().to_vec(), index)
+                    .map_err(|e| self.heap_error(e))?;
+            }
+            Opcode::LDB => {
+                let (index, range) = self.read_heap_access(1)?;
+                let register = self.read_register()?;
+                self.registers[register] =
+                    self.memory_heap.get_slice(index).unwrap_or_default()[range.start] as i32;
+            }
+            Opcode::STB => {
+                let value = self.read_register_value()?;
+                let (index, range) = self.read_heap_access(1)?;
+                self.check_writable(index)?;
+                if let Some(bytes) = self.memory_heap.get_slice_mut(index) {
+                    bytes[range.start] = value as u8;
+                }
+            }
+            Opcode::LDW => {
+                let (index, range) = self.read_heap_access(4)?;
+                let register = self.read_register()?;
+                let bytes = self.memory_heap.get_slice(index).unwrap_or_default();
+                self.registers[register] = LittleEndian::read_i32(&bytes[range]);
+            }
+            Opcode::STW => {
+                let value = self.read_register_value()?;
+                let (index, range) = self.read_heap_access(4)?;
+                self.check_writable(index)?;
+                if let Some(bytes) = self.memory_heap.get_slice_mut(index) {
+                    LittleEndian::write_i32(&mut bytes[range], value);
+                }
+            }
+            Opcode::LDF => {
+                let index = self.read_float_partition()?;
+                let register = self.read_register()?;
+                let bytes = self.memory_heap.get_slice(index).unwrap_or_default();
+                self.float_registers[register] = LittleEndian::read_f64(bytes);
+            }
+            Opcode::NEWP => self.new_partition()?,
+            Opcode::FREE => {
+                let index = self.read_heap_index()?;
+                self.check_writable(index)?;
+                self.memory_heap.free(index);
+            }
+            Opcode::PUSH => self.push()?,
+            Opcode::POP => self.pop()?,
+            Opcode::CALL | Opcode::CALLT => self.call()?,
+            Opcode::RET => self.ret()?,
             Opcode::IGL => {
-                println!("Illegal instruction encountered");
-                return Some(1);
+                return Err(VMError::IllegalOpcode {
+                    pc: self.current_pc,
+                    opcode: byte,
+                });
             }
         }
 
-        None
+        Ok(None)
     }
 
-    fn calculate<F: FnOnce(i32, i32) -> (i32, Option<u32>)>(&mut self, op: F) {
-        let register1 = self.registers[self.program_cursor.read_register_index().unwrap()];
-        let register2 = self.registers[self.program_cursor.read_register_index().unwrap()];
-        let (result, remainder) = op(register1, register2);
-        self.registers[self.program_cursor.read_register_index().unwrap()] = result;
-        if let Some(remainder) = remainder {
+    fn calculate<F: FnOnce(i32, i32) -> i32>(&mut self, op: F) -> Result<(), VMError> {
+        let register1 = self.read_register_value()?;
+        let register2 = self.read_register_value()?;
+        let destination = self.read_register()?;
+        self.registers[destination] = op(register1, register2);
+        Ok(())
+    }
+
+    // Arithmetic that can overflow, the operation returns None on a division by zero
+    fn calculate_checked<F: FnOnce(i32, i32) -> Option<Outcome>>(
+        &mut self,
+        op: F,
+    ) -> Result<(), VMError> {
+        let register1 = self.read_register_value()?;
+        let register2 = self.read_register_value()?;
+        let destination = self.read_register()?;
+        let outcome = op(register1, register2).ok_or(VMError::DivisionByZero {
+            pc: self.current_pc,
+            opcode: self.current_opcode,
+        })?;
+        self.registers[destination] = self.settle(outcome)?;
+        if let Some(remainder) = outcome.remainder {
             self.remainder = remainder
         }
+        Ok(())
+    }
+
+    fn increment<F: FnOnce(i32) -> Outcome>(&mut self, op: F) -> Result<(), VMError> {
+        let register = self.read_register()?;
+        self.registers[register] = self.settle(op(self.registers[register]))?;
+        Ok(())
     }
 
-    fn compare<F: FnOnce(i32, i32) -> bool>(&mut self, comparator: F) {
-        let register1 = self.registers[self.program_cursor.read_register_index().unwrap()];
-        let register2 = self.registers[self.program_cursor.read_register_index().unwrap()];
+    // Updates the overflow flag and applies the arithmetic mode
+    fn settle(&mut self, outcome: Outcome) -> Result<i32, VMError> {
+        self.overflow_flag = outcome.overflowed;
+        self.arithmetic_mode
+            .resolve(&outcome)
+            .ok_or(VMError::ArithmeticOverflow {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+            })
+    }
+
+    fn compare<F: FnOnce(i32, i32) -> bool>(&mut self, comparator: F) -> Result<(), VMError> {
+        let register1 = self.read_register_value()?;
+        let register2 = self.read_register_value()?;
         let equal_flag = comparator(register1, register2);
         self.equal_flag = equal_flag;
         self.program_cursor.next_8_bits();
+        Ok(())
     }
 
-    fn sleep(&mut self, unit: i32) {
-        let register = self.program_cursor.read_register_index().unwrap();
-        let milliseconds = self.registers[register] * unit;
-        thread::sleep(Duration::from_millis(milliseconds as u64));
+    fn calculate_float<F: FnOnce(f64, f64) -> f64>(&mut self, op: F) -> Result<(), VMError> {
+        let register1 = self.read_float_register_value()?;
+        let register2 = self.read_float_register_value()?;
+        let destination = self.read_register()?;
+        self.float_registers[destination] = op(register1, register2);
+        Ok(())
+    }
+
+    fn compare_float<F: FnOnce(f64, f64) -> bool>(&mut self, comparator: F) -> Result<(), VMError> {
+        let register1 = self.read_float_register_value()?;
+        let register2 = self.read_float_register_value()?;
+        self.equal_flag = comparator(register1, register2);
+        self.program_cursor.next_8_bits();
+        Ok(())
     }
 
-    fn ask<T>(&mut self) -> Option<T>
+    fn sleep(&mut self, unit: i64) -> Result<(), VMError> {
+        let value = self.read_register_value()?;
+        let milliseconds = (value as i64 * unit).max(0);
+        self.clock.sleep(Duration::from_millis(milliseconds as u64));
+        Ok(())
+    }
+
+    fn ask<T>(&mut self, prompt: &str) -> Result<Option<T>, VMError>
     where
         T: FromStr,
         T::Err: Debug,
     {
-        let prompt: &str;
-        match self.read_data() {
-            Some(s) => prompt = s,
-            None => {
-                println!("Error decoding string for prts instruction");
-                return None;
+        self.write(prompt)?;
+        let user_input = self.io.read_line().map_err(|e| self.io_error(e))?;
+
+        match user_input.parse::<T>() {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(e) => {
+                self.write(&format!("Error parsing user input: {e:#?}\n"))?;
+                Ok(None)
             }
-        };
+        }
+    }
+
+    fn print(&mut self) -> Result<(), VMError> {
+        let s = self.read_data()?;
+        self.write(&format!("{s}\n"))?;
+        Ok(())
+    }
+
+    fn jump<F: FnOnce(i64, i64, i64, bool) -> i64>(&mut self, jump: F) -> Result<(), VMError> {
+        let value = self.read_target()?;
+        let target = jump(
+            value,
+            self.get_starting_offset()? as i64,
+            self.program_cursor.position() as i64,
+            self.equal_flag,
+        );
+        self.jump_to(target)
+    }
 
-        print!("{prompt}");
-        if let Err(e) = stdout().flush() {
-            println!("Error flusing stdout: {e:#?}");
-            return None;
+    // Register jumps read their target from the register, the others carry it
+    fn read_target(&mut self) -> Result<i64, VMError> {
+        if self.current_opcode.signature() == [OperandKind::CodeOffset] {
+            Ok(self.read_u16()? as i64)
+        } else {
+            Ok(self.read_register_value()? as i64)
         }
+    }
 
-        let mut user_input = String::new();
-        let stdin = io::stdin();
-        if let Err(e) = stdin.read_line(&mut user_input) {
-            println!("Error reading user input: {e:#?}");
-            return None;
+    fn jump_to(&mut self, target: i64) -> Result<(), VMError> {
+        if target < 0 {
+            return Err(VMError::InvalidJumpTarget {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                target,
+            });
         }
+        self.program_cursor.set_position(target as u64);
+        Ok(())
+    }
 
-        user_input = user_input[0..user_input.len() - 1].to_string();
+    fn push(&mut self) -> Result<(), VMError> {
+        let value = self.read_register_value()?;
+        if self.stack.len() >= STACK_SIZE {
+            return Err(VMError::StackOverflow {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+            });
+        }
+        self.stack.push(value);
+        Ok(())
+    }
 
-        match user_input.parse::<T>() {
-            Ok(parsed) => Some(parsed),
-            Err(e) => {
-                println!("Error parsing user input: {e:#?}");
-                None
-            }
+    fn pop(&mut self) -> Result<(), VMError> {
+        let register = self.read_register()?;
+        self.registers[register] = self.stack.pop().ok_or(VMError::StackUnderflow {
+            pc: self.current_pc,
+            opcode: self.current_opcode,
+        })?;
+        Ok(())
+    }
+
+    // Works like JMP, but remembers where to come back to for RET
+    fn call(&mut self) -> Result<(), VMError> {
+        let target = self.read_target()?;
+        let code_start = PIE_HEADER_LENGTH + self.get_starting_offset()?;
+        if self.return_addresses.len() >= STACK_SIZE {
+            return Err(VMError::StackOverflow {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+            });
         }
+        self.return_addresses
+            .push(self.program_cursor.position() as usize);
+        self.jump_to(code_start as i64 + target)
     }
 
-    fn print(&mut self) {
-        match self.read_data() {
-            Some(s) => println!("{s}"),
-            None => println!("Error decoding string for prts instruction"),
-        };
+    fn ret(&mut self) -> Result<(), VMError> {
+        let return_address = self.return_addresses.pop().ok_or(VMError::StackUnderflow {
+            pc: self.current_pc,
+            opcode: self.current_opcode,
+        })?;
+        self.program_cursor.set_position(return_address as u64);
+        Ok(())
     }
 
-    fn jump<F: FnOnce(usize, usize, usize, bool) -> usize>(&mut self, jump: F) {
-        let value = self.registers[self.program_cursor.read_register_index().unwrap()];
-        self.program_cursor.set_position(jump(
-            value as usize,
-            self.get_starting_offset(),
-            self.program_cursor.position() as usize,
-            self.equal_flag,
-        ) as u64);
+    fn alloc(&mut self) -> Result<(), VMError> {
+        let bytes = self.read_register_value()?;
+        if bytes < 0 {
+            return Err(VMError::InvalidAllocation {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                bytes,
+            });
+        }
+        self.memory_heap
+            .alloc(bytes as usize)
+            .map_err(|e| self.heap_full(e))
     }
 
-    fn alloc(&mut self) {
-        let register = self.program_cursor.read_register_index().unwrap();
-        let bytes = self.registers[register];
-        self.memory_heap.alloc(bytes as usize);
+    // Zero filled, within the room reserved by ALOC unless the heap grows
+    fn new_partition(&mut self) -> Result<(), VMError> {
+        let bytes = self.read_register_value()?;
+        let destination = self.read_register()?;
+        if bytes < 0 {
+            return Err(VMError::InvalidAllocation {
+                pc: self.current_pc,
+                opcode: self.current_opcode,
+                bytes,
+            });
+        }
+        let id = self
+            .memory_heap
+            .insert(vec![0; bytes as usize])
+            .map_err(|e| self.heap_full(e))?;
+        self.registers[destination] = id as i32;
+        Ok(())
     }
 
-    fn load(&mut self) {
-        let register = self.program_cursor.read_register_index().unwrap();
-        let number = self.program_cursor.next_16_bits().unwrap() as u32;
+    fn load(&mut self) -> Result<(), VMError> {
+        let register = self.read_register()?;
+        let number = self.read_u16()? as u32;
         self.registers[register] = number as i32;
+        Ok(())
+    }
+
+    fn load_wide(&mut self) -> Result<(), VMError> {
+        let register = self.read_register()?;
+        self.registers[register] = self.read_i32()?;
+        Ok(())
+    }
+
+    fn load_float(&mut self) -> Result<(), VMError> {
+        let register = self.read_register()?;
+        self.float_registers[register] = self.read_f64()?;
+        Ok(())
     }
 }
@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    ops::AddAssign,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    assembler::{symbols::SymbolTable, PIE_HEADER_LENGTH},
+    instruction::Opcode,
+};
+
+use super::VM;
+
+// How many of the most expensive code offsets the report lists
+pub const REPORT_OFFSETS: usize = 20;
+
+// Stands for the code before the first label, or every frame without symbols
+const NO_LABEL: &str = "(no label)";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cost {
+    pub time: Duration,
+    pub count: u64,
+}
+
+impl AddAssign for Cost {
+    fn add_assign(&mut self, other: Self) {
+        self.time += other.time;
+        self.count += other.count;
+    }
+}
+
+// Where a program spent its instructions and its time
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub opcodes: HashMap<Opcode, Cost>,
+    // Keyed by code offset
+    pub offsets: HashMap<usize, (Opcode, Cost)>,
+    // Code offsets of the active calls, outermost first, then of the instruction
+    pub stacks: HashMap<Vec<usize>, Cost>,
+    pub sleeping: Duration,
+    pub waiting_for_input: Duration,
+}
+
+impl Profile {
+    pub fn instructions(&self) -> u64 {
+        self.opcodes.values().map(|cost| cost.count).sum()
+    }
+
+    fn record(&mut self, opcode: Opcode, stack: Vec<usize>, time: Duration) {
+        let cost = Cost { time, count: 1 };
+        *self.opcodes.entry(opcode).or_default() += cost;
+        if let Some(offset) = stack.last() {
+            self.offsets
+                .entry(*offset)
+                .or_insert((opcode, Cost::default()))
+                .1 += cost;
+        }
+        *self.stacks.entry(stack).or_default() += cost;
+
+        match opcode {
+            Opcode::SLP | Opcode::SLPS => self.sleeping += time,
+            Opcode::ASKI | Opcode::ASKS | Opcode::ASKSR => self.waiting_for_input += time,
+            _ => {}
+        }
+    }
+
+    fn label(symbols: Option<&SymbolTable>, offset: usize) -> &str {
+        symbols
+            .and_then(|symbols| symbols.enclosing_label(offset))
+            .unwrap_or(NO_LABEL)
+    }
+
+    pub fn labels(&self, symbols: Option<&SymbolTable>) -> HashMap<String, Cost> {
+        let mut labels: HashMap<String, Cost> = HashMap::new();
+        for (offset, (_, cost)) in &self.offsets {
+            *labels
+                .entry(Self::label(symbols, *offset).to_owned())
+                .or_default() += *cost;
+        }
+        labels
+    }
+
+    // Opcodes, labels and the most expensive offsets, each sorted by time then count
+    pub fn report(&self, symbols: Option<&SymbolTable>) -> String {
+        fn by_cost<K>(costs: impl IntoIterator<Item = (K, Cost)>) -> Vec<(K, Cost)> {
+            let mut costs: Vec<(K, Cost)> = costs.into_iter().collect();
+            costs.sort_by(|(_, a), (_, b)| b.cmp(a));
+            costs
+        }
+
+        let total: Duration = self.opcodes.values().map(|cost| cost.time).sum();
+        let mut output = String::new();
+        writeln!(
+            output,
+            "Executed {} instruction(s) in {total:?}",
+            self.instructions()
+        )
+        .unwrap();
+
+        output.push_str("Opcodes\n");
+        for (opcode, cost) in by_cost(self.opcodes.iter().map(|(o, c)| (*o, *c))) {
+            writeln!(
+                output,
+                "{:>10} {:>14?}  {}",
+                cost.count,
+                cost.time,
+                opcode.mnemonic()
+            )
+            .unwrap();
+        }
+
+        output.push_str("Labels\n");
+        for (label, cost) in by_cost(self.labels(symbols)) {
+            writeln!(output, "{:>10} {:>14?}  {label}", cost.count, cost.time).unwrap();
+        }
+
+        output.push_str("Offsets\n");
+        let offsets = by_cost(
+            self.offsets
+                .iter()
+                .map(|(offset, (opcode, cost))| ((*offset, *opcode), *cost)),
+        );
+        for ((offset, opcode), cost) in offsets.into_iter().take(REPORT_OFFSETS) {
+            writeln!(
+                output,
+                "{:>10} {:>14?}  {offset:>6}: {} in {}",
+                cost.count,
+                cost.time,
+                opcode.mnemonic(),
+                Self::label(symbols, offset)
+            )
+            .unwrap();
+        }
+
+        writeln!(output, "Sleeping: {:?}", self.sleeping).unwrap();
+        writeln!(output, "Waiting for input: {:?}", self.waiting_for_input).unwrap();
+        output
+    }
+
+    // One "label;label;opcode nanoseconds" line per call stack, as flamegraph tools expect
+    pub fn collapsed(&self, symbols: Option<&SymbolTable>) -> String {
+        let mut lines: HashMap<String, u128> = HashMap::new();
+        for (stack, cost) in &self.stacks {
+            let (offset, calls) = match stack.split_last() {
+                Some(split) => split,
+                None => continue,
+            };
+            let mut frames: Vec<&str> = calls
+                .iter()
+                .map(|call| Self::label(symbols, *call))
+                .collect();
+            frames.push(Self::label(symbols, *offset));
+            let opcode = self.offsets[offset].0.mnemonic();
+            *lines
+                .entry(format!("{};{opcode}", frames.join(";")))
+                .or_default() += cost.time.as_nanos();
+        }
+
+        let mut lines: Vec<(String, u128)> = lines.into_iter().collect();
+        lines.sort();
+        lines
+            .into_iter()
+            .map(|(stack, nanos)| format!("{stack} {nanos}\n"))
+            .collect()
+    }
+}
+
+impl VM {
+    // The call stack of the instruction at `pc`, as code offsets
+    pub(super) fn profile_stack(&self, pc: usize) -> Vec<usize> {
+        let code_start = self
+            .get_starting_offset()
+            .map_or(0, |offset| PIE_HEADER_LENGTH + offset);
+        self.return_addresses
+            .iter()
+            // a return address follows its CALL, step back inside it
+            .map(|address| address.saturating_sub(code_start + 1))
+            .chain(std::iter::once(pc.saturating_sub(code_start)))
+            .collect()
+    }
+
+    pub(super) fn profile_instruction(&mut self, pc: usize, stack: Vec<usize>, started: Instant) {
+        let elapsed = started.elapsed();
+        let opcode = match self.program_cursor.get_ref().get(pc) {
+            Some(byte) => Opcode::from(*byte),
+            None => return,
+        };
+        if let Some(profile) = &mut self.profile {
+            profile.record(opcode, stack, elapsed);
+        }
+    }
+}
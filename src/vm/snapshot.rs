@@ -0,0 +1,114 @@
+use std::{
+    fs,
+    io::{self, Cursor},
+    path::Path,
+};
+
+use serde_derive::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{arithmetic::ArithmeticMode, clock::Clock, memory::MemoryHeap, VM};
+
+// Bumped whenever the snapshot layout changes
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+// Everything needed to pick a program up exactly where it stopped, on this machine
+// or another one. Stored as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub id: Uuid,
+    pub registers: [i32; 32],
+    // Bit patterns, JSON has no NaN or infinities
+    pub float_registers: [u64; 32],
+    pub remainder: i32,
+    pub equal_flag: bool,
+    pub overflow_flag: bool,
+    pub arithmetic_mode: ArithmeticMode,
+    pub clock: Clock,
+    pub program: Vec<u8>,
+    pub position: u64,
+    // MemoryHeap::header followed by MemoryHeap::to_bytes
+    pub heap: Vec<u8>,
+    // Ids freed at runtime, stored as empty partitions in `heap`
+    pub freed_partitions: Vec<usize>,
+    pub stack: Vec<i32>,
+    pub return_addresses: Vec<usize>,
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let snapshot: Snapshot = serde_json::from_slice(&fs::read(path)?)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported snapshot version {}", snapshot.version),
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+impl VM {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut heap = self.memory_heap.header();
+        heap.append(&mut self.memory_heap.to_bytes());
+
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            id: self.id,
+            registers: self.registers,
+            float_registers: self.float_registers.map(f64::to_bits),
+            remainder: self.remainder,
+            equal_flag: self.equal_flag,
+            overflow_flag: self.overflow_flag,
+            arithmetic_mode: self.arithmetic_mode,
+            clock: self.clock,
+            program: self.program_cursor.get_ref().clone(),
+            position: self.program_cursor.position(),
+            heap,
+            freed_partitions: self.memory_heap.freed().collect(),
+            stack: self.stack.clone(),
+            return_addresses: self.return_addresses.clone(),
+        }
+    }
+
+    // Replaces the whole execution state. Settings like the budget, io or tracer are kept.
+    pub fn restore(&mut self, snapshot: Snapshot) -> io::Result<()> {
+        if snapshot.heap.len() < 12 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The snapshot heap is missing its header",
+            ));
+        }
+        let (header, bytes) = snapshot.heap.split_at(12);
+        self.replace_heap(MemoryHeap::from_bytes(
+            &mut Cursor::new(bytes),
+            &mut Cursor::new(header),
+        )?);
+        for id in snapshot.freed_partitions {
+            self.memory_heap.free(id);
+        }
+
+        self.id = snapshot.id;
+        self.registers = snapshot.registers;
+        self.float_registers = snapshot.float_registers.map(f64::from_bits);
+        self.remainder = snapshot.remainder;
+        self.equal_flag = snapshot.equal_flag;
+        self.overflow_flag = snapshot.overflow_flag;
+        self.arithmetic_mode = snapshot.arithmetic_mode;
+        self.clock = snapshot.clock;
+        self.program = snapshot.program;
+        self.program_cursor = Cursor::new(self.program.clone());
+        self.program_cursor.set_position(snapshot.position);
+        self.stack = snapshot.stack;
+        self.return_addresses = snapshot.return_addresses;
+        self.paused_at = None;
+        self.clear_history();
+        Ok(())
+    }
+}
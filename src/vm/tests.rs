@@ -39,6 +39,19 @@ mod opcode {
         assert_eq!(test_vm.registers[0], 500);
     }
 
+    #[test]
+    fn test_loadw() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(
+            vec![42, 0, 0, 1, 17, 112, 42, 1, 255, 255, 255, 255],
+            MemoryHeap::new(0),
+        );
+        test_vm.run_once();
+        test_vm.run_once();
+        assert_eq!(test_vm.registers[0], 70000);
+        assert_eq!(test_vm.registers[1], -1);
+    }
+
     mod math {
         use super::*;
 
@@ -82,6 +95,216 @@ mod opcode {
             assert_eq!(test_vm.registers[0], 8);
             assert_eq!(test_vm.remainder, 2);
         }
+
+        #[test]
+        fn test_inc_dec() {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = 41;
+            test_vm.registers[1] = i32::MIN;
+            test_vm.set_program(vec![39, 0, 40, 1], MemoryHeap::new(0));
+            test_vm.run_once();
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[0], 42);
+            assert_eq!(test_vm.registers[1], i32::MAX);
+        }
+
+        #[test]
+        fn test_mod() {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = -50;
+            test_vm.registers[1] = 6;
+            test_vm.set_program(vec![37, 0, 1, 2], MemoryHeap::new(0));
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[2], -2);
+            assert_eq!(test_vm.remainder, -2);
+        }
+
+        #[test]
+        fn test_rem() {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = 50;
+            test_vm.registers[1] = 6;
+            test_vm.set_program(vec![5, 0, 1, 2, 38, 3], MemoryHeap::new(0));
+            test_vm.run_once();
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[2], 8);
+            assert_eq!(test_vm.registers[3], 2);
+        }
+
+        #[test]
+        fn test_wrapping_overflow() {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = i32::MAX;
+            test_vm.registers[1] = 1;
+            test_vm.set_program(vec![2, 0, 1, 2, 2, 1, 1, 3], MemoryHeap::new(0));
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[2], i32::MIN);
+            assert!(test_vm.overflow_flag());
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[3], 2);
+            assert!(!test_vm.overflow_flag());
+        }
+
+        #[test]
+        fn test_saturating_overflow() {
+            let mut test_vm = VM::new();
+            test_vm.arithmetic_mode = ArithmeticMode::Saturating;
+            test_vm.registers[0] = i32::MIN;
+            test_vm.registers[1] = -1;
+            test_vm.set_program(vec![5, 0, 1, 2, 4, 0, 0, 3, 40, 0], MemoryHeap::new(0));
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[2], i32::MAX);
+            assert_eq!(test_vm.remainder, 0);
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[3], i32::MAX);
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[0], i32::MIN);
+            assert!(test_vm.overflow_flag());
+        }
+    }
+
+    mod float {
+        use super::*;
+        use crate::vm::io::MemoryIo;
+
+        fn calculate(opcode: u8, a: f64, b: f64) -> f64 {
+            let mut test_vm = VM::new();
+            test_vm.float_registers[0] = a;
+            test_vm.float_registers[1] = b;
+            test_vm.set_program(vec![opcode, 0, 1, 2], MemoryHeap::new(0));
+            test_vm.run_once();
+            test_vm.float_registers[2]
+        }
+
+        fn compare(opcode: u8, a: f64, b: f64) -> bool {
+            let mut test_vm = VM::new();
+            test_vm.float_registers[0] = a;
+            test_vm.float_registers[1] = b;
+            test_vm.set_program(vec![opcode, 0, 1, 0], MemoryHeap::new(0));
+            test_vm.run_once();
+            test_vm.equal_flag
+        }
+
+        #[test]
+        fn test_loadf() {
+            let mut test_vm = VM::new();
+            let mut program = vec![43, 3];
+            program.extend_from_slice(&(-2.5f64).to_be_bytes());
+            test_vm.set_program(program, MemoryHeap::new(0));
+            test_vm.run_once();
+            assert_eq!(test_vm.float_registers[3], -2.5);
+            assert_eq!(
+                test_vm.program_cursor.position() as usize,
+                PIE_HEADER_LENGTH + 10
+            );
+        }
+
+        #[test]
+        fn test_arithmetic() {
+            assert_eq!(calculate(44, 1.5, 0.25), 1.75);
+            assert_eq!(calculate(45, 1.5, 0.25), 1.25);
+            assert_eq!(calculate(46, 1.5, 0.5), 0.75);
+            assert_eq!(calculate(47, 1.0, 4.0), 0.25);
+            assert!(calculate(47, 1.0, 0.0).is_infinite());
+        }
+
+        #[test]
+        fn test_comparisons() {
+            assert!(compare(48, 0.5, 0.5));
+            assert!(!compare(49, 0.5, 0.5));
+            assert!(compare(50, 1.5, 0.5));
+            assert!(compare(51, 0.5, 1.5));
+            assert!(compare(52, 0.5, 0.5));
+            assert!(!compare(53, 1.5, 0.5));
+            assert!(!compare(48, f64::NAN, f64::NAN));
+        }
+
+        #[test]
+        fn test_conversions() {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = -7;
+            test_vm.float_registers[1] = 3.99;
+            test_vm.float_registers[2] = f64::NAN;
+            test_vm.set_program(vec![54, 0, 0, 55, 1, 1, 55, 2, 2], MemoryHeap::new(0));
+            test_vm.run_once();
+            test_vm.run_once();
+            test_vm.run_once();
+            assert_eq!(test_vm.float_registers[0], -7.0);
+            assert_eq!(test_vm.registers[1], 3);
+            assert_eq!(test_vm.registers[2], 0);
+        }
+
+        #[test]
+        fn test_prtf() {
+            let io = MemoryIo::default();
+            let mut test_vm = VM::new();
+            test_vm.io = Box::new(io.clone());
+            test_vm.float_registers[0] = 0.125;
+            test_vm.set_program(vec![56, 0], MemoryHeap::new(0));
+            test_vm.run_once();
+            assert_eq!(io.output(), "0.125\n");
+        }
+    }
+
+    mod bitwise {
+        use super::*;
+
+        fn calculate(opcode: u8, a: i32, b: i32) -> i32 {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = a;
+            test_vm.registers[1] = b;
+            test_vm.set_program(vec![opcode, 0, 1, 2], MemoryHeap::new(0));
+            test_vm.run_once();
+            test_vm.registers[2]
+        }
+
+        #[test]
+        fn test_and() {
+            assert_eq!(calculate(30, 0b1100, 0b1010), 0b1000);
+        }
+
+        #[test]
+        fn test_or() {
+            assert_eq!(calculate(31, 0b1100, 0b1010), 0b1110);
+        }
+
+        #[test]
+        fn test_xor() {
+            assert_eq!(calculate(32, 0b1100, 0b1010), 0b0110);
+        }
+
+        #[test]
+        fn test_not() {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = 0b1010;
+            test_vm.set_program(vec![33, 0, 1], MemoryHeap::new(0));
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[1], !0b1010);
+            assert_eq!(
+                test_vm.program_cursor.position() as usize,
+                PIE_HEADER_LENGTH + 3
+            );
+        }
+
+        #[test]
+        fn test_shl() {
+            assert_eq!(calculate(34, 3, 4), 48);
+            assert_eq!(calculate(34, 1, 31), i32::MIN);
+            assert_eq!(calculate(34, 1, 33), 2);
+        }
+
+        #[test]
+        fn test_shr() {
+            assert_eq!(calculate(35, 48, 4), 3);
+            assert_eq!(calculate(35, -1, 28), 0xF);
+        }
+
+        #[test]
+        fn test_sar() {
+            assert_eq!(calculate(36, 48, 4), 3);
+            assert_eq!(calculate(36, -16, 2), -4);
+            assert_eq!(calculate(36, i32::MIN, 31), -1);
+        }
     }
 
     mod jump {
@@ -249,6 +472,25 @@ mod opcode {
         }
     }
 
+    #[test]
+    fn test_jneq() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 4;
+        test_vm.equal_flag = false;
+        test_vm.set_program(vec![41, 0, 0, 0, 41, 0], MemoryHeap::new(0));
+        test_vm.run_once();
+        assert_eq!(
+            test_vm.program_cursor.position() as usize,
+            PIE_HEADER_LENGTH + 4
+        );
+        test_vm.equal_flag = true;
+        test_vm.run_once();
+        assert_eq!(
+            test_vm.program_cursor.position() as usize,
+            PIE_HEADER_LENGTH + 6
+        );
+    }
+
     #[test]
     fn test_aloc() {
         let mut test_vm = VM::new();
@@ -263,21 +505,187 @@ mod opcode {
         let mut test_vm = VM::new();
 
         let mut mem = MemoryHeap::new(16);
-        mem.add(vec![72, 101, 108, 108, 111, 0]);
+        mem.add(vec![72, 101, 108, 108, 111, 0]).unwrap();
 
-        test_vm.set_program(vec![17, 0, 0, 0], mem);
+        test_vm.set_program(vec![18, 0, 0], mem);
         test_vm.run_once();
+        assert_eq!(
+            test_vm.program_cursor.position() as usize,
+            PIE_HEADER_LENGTH + 11 + 3
+        );
+    }
+
+    mod heap {
+        use super::*;
+
+        #[test]
+        fn test_ldi_sti() {
+            let mut mem = MemoryHeap::new(4);
+            mem.add((-41i32).to_le_bytes().to_vec()).unwrap();
+            let mut test_vm = VM::new();
+            // ldi #0 $1, dec $1, sti $1 #0
+            test_vm.set_program(vec![63, 0, 0, 1, 40, 1, 64, 1, 0, 0], mem);
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[1], -41);
+            test_vm.run_once();
+            test_vm.run_once();
+            assert_eq!(
+                test_vm.memory_heap.get(0),
+                Some((-42i32).to_le_bytes().to_vec())
+            );
+        }
+
+        #[test]
+        fn test_ldf() {
+            let mut mem = MemoryHeap::new(8);
+            mem.add(2.25f64.to_le_bytes().to_vec()).unwrap();
+            let mut test_vm = VM::new();
+            // ldf #0 $3
+            test_vm.set_program(vec![74, 0, 0, 3], mem);
+            test_vm.run_once();
+            assert_eq!(test_vm.float_registers[3], 2.25);
+        }
+
+        #[test]
+        fn test_newp_free() {
+            let io = crate::vm::io::MemoryIo::default();
+            let mut mem = MemoryHeap::new(2);
+            mem.add("Hi".as_bytes().to_vec()).unwrap();
+            let mut test_vm = VM::new();
+            test_vm.io = Box::new(io.clone());
+            // load $0 #3, newp $0 $1, grpsr $2 $2 $1, prtsr $1, free $1, newp $0 $3
+            test_vm.set_program(
+                vec![1, 0, 0, 3, 65, 0, 1, 69, 2, 2, 1, 67, 1, 66, 1, 65, 0, 3],
+                mem,
+            );
+            test_vm.memory_heap.alloc(8).unwrap();
+            test_vm.run_once();
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[1], 1);
+            assert_eq!(test_vm.memory_heap.get(1), Some(vec![0, 0, 0]));
+            test_vm.run_once();
+            test_vm.run_once();
+            assert_eq!(io.output(), "HiHi\n");
+            test_vm.run_once();
+            assert_eq!(test_vm.memory_heap.get_slice(1), None);
+            assert_eq!(test_vm.memory_heap.get(0), Some("Hi".as_bytes().to_vec()));
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[3], 1);
+        }
+
+        #[test]
+        fn test_byte_and_word_access() {
+            let mut mem = MemoryHeap::new(8);
+            mem.add("Hi".as_bytes().to_vec()).unwrap();
+            mem.add(vec![0; 6]).unwrap();
+            let mut test_vm = VM::new();
+            // ldb $0 $1 $2, stb $2 $0 $0, stw $5 $3 $4, ldw $3 $4 $6
+            test_vm.set_program(
+                vec![70, 0, 1, 2, 71, 2, 0, 0, 73, 5, 3, 4, 72, 3, 4, 6],
+                mem,
+            );
+            test_vm.registers[1] = 1;
+            test_vm.registers[3] = 1;
+            test_vm.registers[4] = 2;
+            test_vm.registers[5] = 0x01020304;
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[2], 'i' as i32);
+            test_vm.run_once();
+            assert_eq!(test_vm.memory_heap.get(0), Some("ii".as_bytes().to_vec()));
+            test_vm.run_once();
+            assert_eq!(test_vm.memory_heap.get(1), Some(vec![0, 0, 4, 3, 2, 1]));
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[6], 0x01020304);
+        }
+    }
+
+    mod stack {
+        use super::*;
+
+        #[test]
+        fn test_push_pop() {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = 7;
+            test_vm.registers[1] = -3;
+            test_vm.set_program(vec![26, 0, 26, 1, 27, 2, 27, 3], MemoryHeap::new(0));
+            test_vm.run_once();
+            test_vm.run_once();
+            assert_eq!(test_vm.stack, vec![7, -3]);
+            test_vm.run_once();
+            test_vm.run_once();
+            assert_eq!(test_vm.registers[2], -3);
+            assert_eq!(test_vm.registers[3], 7);
+            assert!(test_vm.stack.is_empty());
+        }
+
+        #[test]
+        fn test_call_ret() {
+            let mut test_vm = VM::new();
+            test_vm.registers[0] = 3;
+            test_vm.set_program(vec![28, 0, 0, 1, 1, 0, 9, 29], MemoryHeap::new(0));
+            let events = test_vm.run();
+            assert_eq!(test_vm.registers[1], 9);
+            assert!(test_vm.return_addresses.is_empty());
+            assert_eq!(
+                events.last().map(|e| e.event().clone()),
+                Some(VMEventType::GracefulStop { code: 0 })
+            );
+        }
+
+        #[test]
+        fn test_stack_overflow() {
+            let mut test_vm = VM::new();
+            test_vm.set_program(vec![28, 0], MemoryHeap::new(0));
+            let events = test_vm.run();
+            assert_eq!(
+                events.last().map(|e| e.event().clone()),
+                Some(VMEventType::Crash {
+                    error: VMError::StackOverflow {
+                        pc: PIE_HEADER_LENGTH,
+                        opcode: Opcode::CALL
+                    },
+                    pc: PIE_HEADER_LENGTH
+                })
+            );
+            assert_eq!(test_vm.return_addresses.len(), STACK_SIZE);
+        }
+
+        #[test]
+        fn test_stack_underflow() {
+            let mut test_vm = VM::new();
+            test_vm.set_program(vec![27, 0], MemoryHeap::new(0));
+            match test_vm.run().last().map(|e| e.event().clone()) {
+                Some(VMEventType::Crash {
+                    error: VMError::StackUnderflow { opcode, .. },
+                    ..
+                }) => assert_eq!(opcode, Opcode::POP),
+                event => panic!("Expected a stack underflow, got {event:?}"),
+            }
+
+            let mut test_vm = VM::new();
+            test_vm.set_program(vec![29], MemoryHeap::new(0));
+            match test_vm.run().last().map(|e| e.event().clone()) {
+                Some(VMEventType::Crash {
+                    error: VMError::StackUnderflow { opcode, .. },
+                    ..
+                }) => assert_eq!(opcode, Opcode::RET),
+                event => panic!("Expected a stack underflow, got {event:?}"),
+            }
+        }
     }
 
     mod time {
+        use std::time::Duration;
+
         use super::*;
+        use crate::vm::clock::Clock;
         use chrono::Utc;
 
         #[test]
         fn test_slp_opcode() {
             let mut test_vm = VM::new();
             test_vm.registers[0] = 100;
-            test_vm.set_program(vec![18, 0], MemoryHeap::new(0));
+            test_vm.set_program(vec![19, 0], MemoryHeap::new(0));
             let start = Utc::now().timestamp_millis();
             test_vm.run_once();
             assert!(Utc::now().timestamp_millis() - start >= 100);
@@ -287,10 +695,1067 @@ mod opcode {
         fn test_slps_opcode() {
             let mut test_vm = VM::new();
             test_vm.registers[0] = 1;
-            test_vm.set_program(vec![19, 0], MemoryHeap::new(0));
+            test_vm.set_program(vec![20, 0], MemoryHeap::new(0));
             let start = Utc::now().timestamp_millis();
             test_vm.run_once();
             assert!(Utc::now().timestamp_millis() - start >= 1000);
         }
+
+        #[test]
+        fn test_virtual_clock() {
+            let mut test_vm = VM::new();
+            test_vm.clock = Clock::virtual_clock();
+            test_vm.set_history_cap(4);
+            test_vm.registers[0] = 3600;
+            test_vm.registers[1] = 250;
+            test_vm.set_program(vec![20, 0, 19, 1], MemoryHeap::new(0));
+            let start = Utc::now().timestamp_millis();
+            let events = test_vm.run();
+            assert!(Utc::now().timestamp_millis() - start < 1000);
+
+            let expected = Duration::from_secs(3600) + Duration::from_millis(250);
+            assert_eq!(test_vm.virtual_time(), Some(expected));
+            assert_eq!(events[0].virtual_time(), Some(Duration::ZERO));
+            assert_eq!(events.last().unwrap().virtual_time(), Some(expected));
+
+            assert!(test_vm.step_back());
+            assert!(test_vm.step_back());
+            assert!(test_vm.step_back());
+            assert_eq!(test_vm.virtual_time(), Some(Duration::ZERO));
+        }
+
+        #[test]
+        fn test_real_clock_events() {
+            let mut test_vm = VM::new();
+            test_vm.set_program(vec![0], MemoryHeap::new(0));
+            let events = test_vm.run();
+            assert_eq!(test_vm.virtual_time(), None);
+            assert!(events.iter().all(|event| event.virtual_time().is_none()));
+        }
+    }
+}
+
+mod io {
+    use super::*;
+    use crate::vm::io::MemoryIo;
+
+    #[test]
+    fn test_prti_output() {
+        let io = MemoryIo::default();
+        let mut test_vm = VM::new();
+        test_vm.io = Box::new(io.clone());
+        test_vm.registers[3] = -42;
+        test_vm.set_program(vec![17, 3], MemoryHeap::new(0));
+        test_vm.run_once();
+        assert_eq!(io.output(), "-42\n");
+    }
+
+    #[test]
+    fn test_prts_output() {
+        let mut mem = MemoryHeap::new(16);
+        mem.add("Name? ".as_bytes().to_vec()).unwrap();
+        mem.add(vec![]).unwrap();
+        let io = MemoryIo::default();
+        let mut test_vm = VM::new();
+        test_vm.io = Box::new(io.clone());
+        test_vm.set_program(vec![18, 0, 0], mem);
+        test_vm.run_once();
+        assert_eq!(io.output(), "Name? \n");
+    }
+
+    #[test]
+    fn test_aski_input() {
+        let mut mem = MemoryHeap::new(16);
+        mem.add("Name? ".as_bytes().to_vec()).unwrap();
+        mem.add(vec![]).unwrap();
+        let io = MemoryIo::new(&["27", "not a number"]);
+        let mut test_vm = VM::new();
+        test_vm.io = Box::new(io.clone());
+        test_vm.set_program(vec![21, 0, 0, 4, 21, 0, 0, 5], mem);
+        test_vm.run_once();
+        test_vm.run_once();
+        assert_eq!(test_vm.registers[4], 27);
+        assert_eq!(test_vm.registers[5], 0);
+        assert!(io
+            .output()
+            .starts_with("Name? Name? Error parsing user input"));
+    }
+
+    #[test]
+    fn test_asks_input() {
+        let mut mem = MemoryHeap::new(16);
+        mem.add("Name? ".as_bytes().to_vec()).unwrap();
+        mem.add(vec![]).unwrap();
+        let io = MemoryIo::new(&["Alice"]);
+        let mut test_vm = VM::new();
+        test_vm.io = Box::new(io.clone());
+        test_vm.set_program(vec![22, 0, 0, 0, 1, 0], mem);
+        test_vm.run();
+        assert_eq!(
+            test_vm.memory_heap.get(1),
+            Some("Alice".as_bytes().to_vec())
+        );
+        assert_eq!(io.output(), "Name? ");
+    }
+
+    #[test]
+    fn test_exhausted_input() {
+        let mut mem = MemoryHeap::new(16);
+        mem.add("Name? ".as_bytes().to_vec()).unwrap();
+        mem.add(vec![]).unwrap();
+        let mut test_vm = VM::new();
+        test_vm.io = Box::new(MemoryIo::default());
+        test_vm.set_program(vec![22, 0, 0, 0, 1], mem);
+        let events = test_vm.run();
+        match events.last().map(|e| e.event().clone()) {
+            Some(VMEventType::Crash {
+                error: VMError::Io { opcode, .. },
+                ..
+            }) => assert_eq!(opcode, Opcode::ASKS),
+            event => panic!("Expected an io crash, got {event:?}"),
+        }
+    }
+}
+
+mod fault {
+    use super::*;
+    use crate::vm::{
+        io::{MemoryIo, VMIo},
+        memory::HeapLimit,
+    };
+
+    fn crash(test_vm: &mut VM) -> Option<(VMError, usize)> {
+        match test_vm.run().last().map(|e| e.event().clone()) {
+            Some(VMEventType::Crash { error, pc }) => Some((error, pc)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut test_vm = VM::new();
+        test_vm.add_bytes(vec![1, 2, 3]);
+        assert_eq!(crash(&mut test_vm), Some((VMError::InvalidHeader, 0)));
+    }
+
+    #[test]
+    fn test_illegal_opcode() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![200], MemoryHeap::new(0));
+        assert_eq!(
+            crash(&mut test_vm),
+            Some((
+                VMError::IllegalOpcode {
+                    pc: PIE_HEADER_LENGTH,
+                    opcode: 200
+                },
+                PIE_HEADER_LENGTH
+            ))
+        );
+    }
+
+    #[test]
+    fn test_invalid_register() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![1, 0, 0, 1, 2, 0, 32, 0], MemoryHeap::new(0));
+        let (error, pc) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::InvalidRegister {
+                pc: PIE_HEADER_LENGTH + 4,
+                opcode: Opcode::ADD,
+                register: 32
+            }
+        );
+        assert_eq!(pc, PIE_HEADER_LENGTH + 4);
+        assert_eq!(test_vm.registers[0], 1);
+    }
+
+    #[test]
+    fn test_missing_operand() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![1, 0, 1], MemoryHeap::new(0));
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::MissingOperand {
+                pc: PIE_HEADER_LENGTH,
+                opcode: Opcode::LOAD
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_heap_index() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![18, 0, 3], MemoryHeap::new(0));
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::InvalidHeapIndex {
+                pc: PIE_HEADER_LENGTH,
+                opcode: Opcode::PRTS,
+                index: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_handle() {
+        let mut mem = MemoryHeap::new(2);
+        mem.add(vec![1, 2]).unwrap();
+        for (program, handle) in [(vec![66, 0, 67, 0], 0), (vec![67, 1], -1)] {
+            let mut test_vm = VM::new();
+            test_vm.registers[1] = -1;
+            test_vm.set_program(program, mem.clone());
+            let (error, _) = crash(&mut test_vm).unwrap();
+            assert_eq!(
+                error,
+                VMError::InvalidHandle {
+                    pc: PIE_HEADER_LENGTH + if handle == 0 { 9 } else { 7 },
+                    opcode: Opcode::PRTSR,
+                    handle
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut mem = MemoryHeap::new(2);
+        mem.add(vec![1, 2]).unwrap();
+        for (program, offset) in [(vec![72, 0, 1, 2], 0), (vec![71, 0, 0, 2], -1)] {
+            let mut test_vm = VM::new();
+            test_vm.registers[2] = offset;
+            test_vm.set_program(program.clone(), mem.clone());
+            let (error, _) = crash(&mut test_vm).unwrap();
+            assert_eq!(
+                error,
+                VMError::OutOfBounds {
+                    pc: PIE_HEADER_LENGTH + 7,
+                    opcode: Opcode::from(program[0]),
+                    index: 0,
+                    offset,
+                    size: 2
+                }
+            );
+            assert_eq!(test_vm.memory_heap.get(0), Some(vec![1, 2]));
+        }
+    }
+
+    #[test]
+    fn test_read_only_partition() {
+        let mut mem = MemoryHeap::new(4);
+        mem.add(vec![1, 2, 3, 4]).unwrap();
+        mem.set_read_only(0);
+        // sti $0 #0, stb $0 $0 $0, free $0
+        for program in [vec![64, 0, 0, 0], vec![71, 0, 0, 0], vec![66, 0]] {
+            let mut test_vm = VM::new();
+            test_vm.set_program(program.clone(), mem.clone());
+            let (error, _) = crash(&mut test_vm).unwrap();
+            assert_eq!(
+                error,
+                VMError::ReadOnlyPartition {
+                    pc: PIE_HEADER_LENGTH + 9,
+                    opcode: Opcode::from(program[0]),
+                    index: 0
+                }
+            );
+            assert_eq!(test_vm.memory_heap.get(0), Some(vec![1, 2, 3, 4]));
+        }
+    }
+
+    #[test]
+    fn test_ask_checks_destination_first() {
+        let mut mem = MemoryHeap::new(4);
+        mem.add("? ".as_bytes().to_vec()).unwrap();
+        mem.add(vec![1, 2]).unwrap();
+        mem.set_read_only(1);
+        // asks #0 #1 into .rodata, asksr $0 $1 with an invalid handle
+        for program in [vec![22, 0, 0, 0, 1], vec![68, 0, 1]] {
+            let io = MemoryIo::new(&["Alice"]);
+            let mut test_vm = VM::new();
+            test_vm.registers[1] = -1;
+            test_vm.io = Box::new(io.clone());
+            test_vm.set_program(program, mem.clone());
+            assert!(crash(&mut test_vm).is_some());
+            assert_eq!(io.output(), "");
+            assert_eq!(io.clone().read_line().unwrap(), "Alice");
+        }
+    }
+
+    #[test]
+    fn test_newp_beyond_capacity() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 3;
+        test_vm.set_program(vec![65, 0, 1], MemoryHeap::new(2));
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::HeapFull {
+                pc: PIE_HEADER_LENGTH,
+                opcode: Opcode::NEWP,
+                requested: 3,
+                available: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_heap_full() {
+        let mut mem = MemoryHeap::new(4);
+        mem.add("abc".as_bytes().to_vec()).unwrap();
+        let mut test_vm = VM::new();
+        // grps #0 #0 #0
+        test_vm.set_program(vec![23, 0, 0, 0, 0, 0, 0], mem);
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::HeapFull {
+                pc: PIE_HEADER_LENGTH + 8,
+                opcode: Opcode::GRPS,
+                requested: 3,
+                available: 1
+            }
+        );
+        assert_eq!(test_vm.memory_heap.get(0), Some("abc".as_bytes().to_vec()));
+    }
+
+    #[test]
+    fn test_grow_heap_on_demand() {
+        let limit = HeapLimit {
+            max_size: Some(6),
+            grow: true,
+        };
+        let mut mem = MemoryHeap::new(4);
+        mem.add("abc".as_bytes().to_vec()).unwrap();
+        let mut test_vm = VM::new();
+        test_vm.memory_heap.set_limit(limit);
+        // grps #0 #0 #0
+        test_vm.set_program(vec![23, 0, 0, 0, 0, 0, 0], mem.clone());
+        test_vm.run();
+        assert_eq!(
+            test_vm.memory_heap.get(0),
+            Some("abcabc".as_bytes().to_vec())
+        );
+        assert_eq!(test_vm.memory_heap.limit(), limit);
+
+        let mut test_vm = VM::new();
+        test_vm.memory_heap.set_limit(HeapLimit {
+            max_size: Some(5),
+            grow: true,
+        });
+        test_vm.set_program(vec![23, 0, 0, 0, 0, 0, 0], mem);
+        assert!(matches!(
+            crash(&mut test_vm),
+            Some((VMError::HeapFull { available: 2, .. }, _))
+        ));
+    }
+
+    #[test]
+    fn test_wrong_partition_size() {
+        let mut test_vm = VM::new();
+        let mut mem = MemoryHeap::new(2);
+        mem.add(vec![1, 2]).unwrap();
+        test_vm.set_program(vec![64, 0, 0, 0], mem);
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::WrongPartitionSize {
+                pc: PIE_HEADER_LENGTH + 7,
+                opcode: Opcode::STI,
+                index: 0,
+                size: 2,
+                expected: 4
+            }
+        );
+        assert_eq!(test_vm.memory_heap.get(0), Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_wrong_float_partition_size() {
+        let mut test_vm = VM::new();
+        let mut mem = MemoryHeap::new(2);
+        mem.add(vec![1, 2]).unwrap();
+        test_vm.set_program(vec![74, 0, 0, 0], mem);
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::WrongPartitionSize {
+                pc: PIE_HEADER_LENGTH + 7,
+                opcode: Opcode::LDF,
+                index: 0,
+                size: 2,
+                expected: 8
+            }
+        );
+        assert_eq!(test_vm.float_registers[0], 0.0);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut test_vm = VM::new();
+        let mut mem = MemoryHeap::new(4);
+        mem.add(vec![72, 101]).unwrap();
+        mem.add(vec![0xff, 0xfe]).unwrap();
+        test_vm.set_program(vec![24, 0, 0, 0, 1], mem);
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::InvalidUtf8 {
+                pc: PIE_HEADER_LENGTH + 14,
+                opcode: Opcode::EQS,
+                index: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![1, 0, 0, 7, 5, 0, 1, 2], MemoryHeap::new(0));
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::DivisionByZero {
+                pc: PIE_HEADER_LENGTH + 4,
+                opcode: Opcode::DIV
+            }
+        );
+        assert_eq!(test_vm.registers[2], 0);
+    }
+
+    #[test]
+    fn test_mod_by_zero() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![37, 0, 1, 2], MemoryHeap::new(0));
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::DivisionByZero {
+                pc: PIE_HEADER_LENGTH,
+                opcode: Opcode::MOD
+            }
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_overflow() {
+        let mut test_vm = VM::new();
+        test_vm.arithmetic_mode = ArithmeticMode::Trapping;
+        test_vm.set_program(vec![1, 0, 255, 255, 4, 0, 0, 1], MemoryHeap::new(0));
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::ArithmeticOverflow {
+                pc: PIE_HEADER_LENGTH + 4,
+                opcode: Opcode::MUL
+            }
+        );
+        assert_eq!(test_vm.registers[1], 0);
+        assert!(test_vm.overflow_flag());
+    }
+
+    #[test]
+    fn test_invalid_jump_target() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![1, 0, 0, 100, 8, 0], MemoryHeap::new(0));
+        let (error, _) = crash(&mut test_vm).unwrap();
+        assert_eq!(
+            error,
+            VMError::InvalidJumpTarget {
+                pc: PIE_HEADER_LENGTH + 4,
+                opcode: Opcode::JMPB,
+                target: PIE_HEADER_LENGTH as i64 + 6 - 100
+            }
+        );
+    }
+
+    #[test]
+    fn test_graceful_stop() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![0], MemoryHeap::new(0));
+        let events = test_vm.run();
+        assert_eq!(
+            events.last().map(|e| e.event().clone()),
+            Some(VMEventType::GracefulStop { code: 0 })
+        );
+    }
+}
+
+mod budget {
+    use super::*;
+    use crate::vm::budget::Budget;
+    use std::time::Duration;
+
+    #[test]
+    fn test_instruction_budget() {
+        let mut test_vm = VM::new();
+        // jmpb $0 followed by hlt, loops forever while $0 is 2
+        test_vm.set_program(vec![8, 0, 0], MemoryHeap::new(0));
+        test_vm.registers[0] = 2;
+        test_vm.budget = Budget::instructions(10);
+        let events = test_vm.run();
+        assert_eq!(
+            events.last().map(|e| e.event().clone()),
+            Some(VMEventType::BudgetExhausted {
+                executed: 10,
+                pc: PIE_HEADER_LENGTH
+            })
+        );
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut test_vm = VM::new();
+        // jmpb $0 followed by hlt, loops forever while $0 is 2
+        test_vm.set_program(vec![8, 0, 0], MemoryHeap::new(0));
+        test_vm.registers[0] = 2;
+        test_vm.budget = Budget::timeout(Duration::from_millis(20));
+        let events = test_vm.run();
+        assert!(matches!(
+            events.last().map(|e| e.event()),
+            Some(VMEventType::BudgetExhausted { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut test_vm = VM::new();
+        // jmpb $0 followed by hlt, loops forever while $0 is 2
+        test_vm.set_program(vec![8, 0, 0], MemoryHeap::new(0));
+        test_vm.registers[0] = 2;
+        test_vm.budget = Budget::instructions(5);
+        test_vm.run();
+        let events = test_vm.resume();
+        assert_eq!(
+            events.last().map(|e| e.event().clone()),
+            Some(VMEventType::BudgetExhausted {
+                executed: 5,
+                pc: PIE_HEADER_LENGTH
+            })
+        );
+
+        test_vm.registers[0] = 0;
+        let events = test_vm.resume();
+        assert_eq!(
+            events.last().map(|e| e.event().clone()),
+            Some(VMEventType::GracefulStop { code: 0 })
+        );
+    }
+
+    #[test]
+    fn test_unlimited_budget() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![1, 0, 0, 7, 0], MemoryHeap::new(0));
+        let events = test_vm.run();
+        assert!(test_vm.budget.is_unlimited());
+        assert_eq!(
+            events.last().map(|e| e.event().clone()),
+            Some(VMEventType::GracefulStop { code: 0 })
+        );
+        assert_eq!(test_vm.registers[0], 7);
+    }
+}
+
+mod debugger {
+    use super::*;
+    use crate::{
+        assembler::symbols::{Symbol, SymbolTable, SymbolType},
+        vm::debugger::StepResult,
+    };
+
+    #[test]
+    fn test_step() {
+        let mut test_vm = VM::new();
+        // load $0 #1, inc $0, inc $0, hlt
+        test_vm.set_program(vec![1, 0, 0, 1, 39, 0, 39, 0, 0], MemoryHeap::new(0));
+        assert_eq!(test_vm.start(), Ok(()));
+        assert_eq!(test_vm.code_offset(), Some(0));
+        assert_eq!(test_vm.run_once(), StepResult::Continued);
+        assert_eq!(test_vm.code_offset(), Some(4));
+        assert_eq!(test_vm.run_once(), StepResult::Continued);
+        assert_eq!(test_vm.run_once(), StepResult::Continued);
+        assert_eq!(test_vm.run_once(), StepResult::Halted(0));
+        assert_eq!(test_vm.registers[0], 3);
+    }
+
+    #[test]
+    fn test_step_fault() {
+        let mut test_vm = VM::new();
+        test_vm.set_program(vec![200], MemoryHeap::new(0));
+        test_vm.start().unwrap();
+        assert_eq!(
+            test_vm.run_once(),
+            StepResult::Faulted(VMError::IllegalOpcode {
+                pc: PIE_HEADER_LENGTH,
+                opcode: 200
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_jump_without_header() {
+        // jmp $0, then callt 0, as typed into the REPL
+        for program in [vec![6, 0], vec![62, 0, 0]] {
+            let mut test_vm = VM::new();
+            test_vm.add_bytes(program);
+            assert_eq!(
+                test_vm.run_once(),
+                StepResult::Faulted(VMError::InvalidHeader)
+            );
+        }
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut test_vm = VM::new();
+        // load $0 #1, inc $0, inc $0, hlt
+        test_vm.set_program(vec![1, 0, 0, 1, 39, 0, 39, 0, 0], MemoryHeap::new(0));
+        assert!(test_vm.add_breakpoint(6));
+        assert!(test_vm.add_breakpoint(8));
+        assert!(!test_vm.add_breakpoint(8));
+
+        let events = test_vm.run();
+        assert_eq!(
+            events.last().map(|e| e.event().clone()),
+            Some(VMEventType::BreakpointHit { offset: 6 })
+        );
+        assert_eq!(test_vm.registers[0], 2);
+
+        assert_eq!(test_vm.continue_execution(), StepResult::Breakpoint(8));
+        assert_eq!(test_vm.registers[0], 3);
+
+        assert!(test_vm.remove_breakpoint(6));
+        assert_eq!(test_vm.breakpoints().collect::<Vec<_>>(), vec![8]);
+        assert_eq!(test_vm.continue_execution(), StepResult::Halted(0));
+    }
+
+    #[test]
+    fn test_breakpoint_after_step() {
+        let mut test_vm = VM::new();
+        // load $0 #1, inc $0, inc $0, hlt
+        test_vm.set_program(vec![1, 0, 0, 1, 39, 0, 39, 0, 0], MemoryHeap::new(0));
+        test_vm.add_breakpoint(4);
+        test_vm.start().unwrap();
+        assert_eq!(test_vm.continue_execution(), StepResult::Breakpoint(4));
+        assert_eq!(test_vm.run_once(), StepResult::Continued);
+        test_vm.clear_breakpoints();
+        assert_eq!(test_vm.continue_execution(), StepResult::Halted(0));
+    }
+
+    #[test]
+    fn test_label_breakpoint() {
+        let mut symbols = SymbolTable::new();
+        symbols.add_symbol(Symbol::new("twice".to_string(), SymbolType::Label, 6));
+        symbols.add_symbol(Symbol::new("hello".to_string(), SymbolType::Data, 4));
+        let mut test_vm = VM::new();
+        // load $0 #1, inc $0, inc $0, hlt
+        test_vm.set_program(vec![1, 0, 0, 1, 39, 0, 39, 0, 0], MemoryHeap::new(0));
+        assert_eq!(test_vm.add_label_breakpoint("twice", &symbols), Some(6));
+        assert_eq!(test_vm.add_label_breakpoint("thrice", &symbols), None);
+        assert_eq!(test_vm.add_label_breakpoint("hello", &symbols), None);
+        test_vm.start().unwrap();
+        assert_eq!(test_vm.continue_execution(), StepResult::Breakpoint(6));
+    }
+
+    #[test]
+    fn test_budget_while_debugging() {
+        let mut test_vm = VM::new();
+        // load $0 #1, inc $0, inc $0, hlt
+        test_vm.set_program(vec![1, 0, 0, 1, 39, 0, 39, 0, 0], MemoryHeap::new(0));
+        test_vm.budget = Budget::instructions(2);
+        test_vm.start().unwrap();
+        assert_eq!(
+            test_vm.continue_execution(),
+            StepResult::BudgetExhausted { executed: 2 }
+        );
+        assert_eq!(test_vm.code_offset(), Some(6));
+    }
+}
+
+mod trace {
+    use super::*;
+    use crate::vm::{
+        io::MemoryIo,
+        trace::{TraceFormat, Tracer},
+    };
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(str::to_owned)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_human_trace() {
+        let mut mem = MemoryHeap::new(2);
+        mem.add("Hi".as_bytes().to_vec()).unwrap();
+        let mut test_vm = VM::new();
+        // load $0 #7, eq $0 $0, prts #0, hlt
+        test_vm.set_program(vec![1, 0, 0, 7, 9, 0, 0, 0, 18, 0, 0, 0], mem);
+        test_vm.io = Box::new(MemoryIo::default());
+        let buffer = SharedBuffer::default();
+        test_vm.tracer = Some(Tracer::new(TraceFormat::Human, buffer.clone()));
+        test_vm.run();
+        assert_eq!(
+            buffer.lines(),
+            vec![
+                "     0: load $0 #7 ; $0 = 7, equal = false",
+                "     4: eq $0 $0 ; equal = true",
+                "     8: prts #0 ; equal = true, heap [0]",
+                "    11: hlt ; equal = true",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_trace() {
+        let mut mem = MemoryHeap::new(2);
+        mem.add("Hi".as_bytes().to_vec()).unwrap();
+        let mut test_vm = VM::new();
+        // load $0 #7, eq $0 $0, prts #0, hlt
+        test_vm.set_program(vec![1, 0, 0, 7, 9, 0, 0, 0, 18, 0, 0, 0], mem);
+        test_vm.io = Box::new(MemoryIo::default());
+        let buffer = SharedBuffer::default();
+        test_vm.tracer = Some(Tracer::new(TraceFormat::Json, buffer.clone()));
+        test_vm.run();
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[0],
+            r##"{"offset":0,"opcode":"load","operands":["$0","#7"],"registers":{"0":7},"float_registers":{},"equal_flag":false,"heap":[]}"##
+        );
+        assert!(lines[2].contains(r#""heap":[0]"#));
+    }
+
+    #[test]
+    fn test_trace_float_registers() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 3;
+        test_vm.set_program(vec![54, 0, 1], MemoryHeap::new(0));
+        let buffer = SharedBuffer::default();
+        test_vm.tracer = Some(Tracer::new(TraceFormat::Human, buffer.clone()));
+        test_vm.run_once();
+        assert_eq!(
+            buffer.lines(),
+            vec!["     0: itof $0 $1 ; $f1 = 3.0, equal = false"]
+        );
+    }
+
+    #[test]
+    fn test_trace_handles() {
+        let mut mem = MemoryHeap::new(3);
+        mem.add("Hi".as_bytes().to_vec()).unwrap();
+        mem.add(vec![5]).unwrap();
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 1;
+        // ldb $0 $1 $0 overwrites the handle it reads
+        test_vm.set_program(vec![70, 0, 1, 0], mem);
+        let buffer = SharedBuffer::default();
+        test_vm.tracer = Some(Tracer::new(TraceFormat::Json, buffer.clone()));
+        test_vm.run_once();
+        assert_eq!(test_vm.registers[0], 5);
+        assert!(buffer.lines()[0].contains(r#""heap":[1]"#));
+    }
+
+    #[test]
+    fn test_trace_new_partition() {
+        let mut test_vm = VM::new();
+        test_vm.registers[0] = 2;
+        test_vm.registers[1] = 7;
+        // newp $0 $1
+        test_vm.set_program(vec![65, 0, 1], MemoryHeap::new(2));
+        let buffer = SharedBuffer::default();
+        test_vm.tracer = Some(Tracer::new(TraceFormat::Json, buffer.clone()));
+        test_vm.run_once();
+        assert_eq!(test_vm.registers[1], 0);
+        assert!(buffer.lines()[0].contains(r#""heap":[0]"#));
+    }
+}
+
+mod snapshot {
+    use super::*;
+    use crate::vm::{
+        debugger::StepResult,
+        io::MemoryIo,
+        snapshot::{Snapshot, SNAPSHOT_VERSION},
+    };
+
+    #[test]
+    fn test_restore_snapshot() {
+        let mut mem = MemoryHeap::new(2);
+        mem.add("Hi".as_bytes().to_vec()).unwrap();
+        let mut original = VM::new();
+        // load $0 #1, inc $0, inc $0, prts #0, hlt
+        original.set_program(vec![1, 0, 0, 1, 39, 0, 39, 0, 18, 0, 0, 0], mem);
+        original.start().unwrap();
+        original.run_once();
+        original.run_once();
+        original.float_registers[1] = f64::NAN;
+        let snapshot = original.snapshot();
+
+        let mut restored = VM::new();
+        restored.io = Box::new(MemoryIo::default());
+        restored.restore(snapshot.clone()).unwrap();
+        assert_eq!(restored.snapshot(), snapshot);
+        assert!(restored.float_registers[1].is_nan());
+
+        original.io = Box::new(MemoryIo::default());
+        assert_eq!(original.continue_execution(), StepResult::Halted(0));
+        assert_eq!(restored.continue_execution(), StepResult::Halted(0));
+        assert_eq!(restored.registers, original.registers);
+        assert_eq!(restored.snapshot(), original.snapshot());
+    }
+
+    #[test]
+    fn test_save_and_load_snapshot() {
+        let mut mem = MemoryHeap::new(2);
+        mem.add("Hi".as_bytes().to_vec()).unwrap();
+        let mut test_vm = VM::new();
+        // load $0 #1, inc $0, prts #0, hlt
+        test_vm.set_program(vec![1, 0, 0, 1, 39, 0, 18, 0, 0, 0], mem);
+        test_vm.start().unwrap();
+        test_vm.run_once();
+        test_vm.float_registers[1] = f64::NAN;
+        let snapshot = test_vm.snapshot();
+        let path = std::env::temp_dir().join(format!("rocky-snapshot-{}.json", snapshot.id));
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_restore_freed_partitions() {
+        let mut mem = MemoryHeap::new(4);
+        mem.add(vec![1]).unwrap();
+        mem.add(vec![2, 3]).unwrap();
+        mem.free(0);
+        let mut original = VM::new();
+        original.set_program(vec![0], mem);
+
+        let mut restored = VM::new();
+        restored.restore(original.snapshot()).unwrap();
+        assert_eq!(restored.memory_heap.get_slice(0), None);
+        assert_eq!(restored.memory_heap.get(1), Some(vec![2, 3]));
+        assert_eq!(restored.snapshot(), original.snapshot());
+    }
+
+    #[test]
+    fn test_unsupported_snapshot_version() {
+        let mut snapshot = VM::new().snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        let path = std::env::temp_dir().join(format!("rocky-snapshot-{}.json", snapshot.id));
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+}
+
+mod history {
+    use super::*;
+    use crate::vm::{debugger::StepResult, io::MemoryIo};
+
+    #[test]
+    fn test_step_back() {
+        let mut test_vm = VM::new();
+        test_vm.set_history_cap(16);
+        // load $0 #1, push $0, inc $0, pop $1, hlt
+        test_vm.set_program(vec![1, 0, 0, 1, 26, 0, 39, 0, 27, 1, 0], MemoryHeap::new(0));
+        test_vm.start().unwrap();
+        assert_eq!(test_vm.continue_execution(), StepResult::Halted(0));
+        assert_eq!(test_vm.executed(), 5);
+        assert_eq!(test_vm.registers[1], 1);
+
+        assert!(test_vm.step_back());
+        assert!(test_vm.step_back());
+        assert_eq!(test_vm.code_offset(), Some(8));
+        assert_eq!(test_vm.registers[1], 0);
+        assert_eq!(test_vm.stack, vec![1]);
+        assert!(test_vm.step_back());
+        assert_eq!(test_vm.registers[0], 1);
+
+        assert_eq!(test_vm.rewind(0), 2);
+        assert_eq!(test_vm.code_offset(), Some(0));
+        assert_eq!(test_vm.registers, [0; 32]);
+        assert!(test_vm.stack.is_empty());
+        assert!(!test_vm.step_back());
+
+        assert_eq!(test_vm.continue_execution(), StepResult::Halted(0));
+        assert_eq!(test_vm.registers[1], 1);
+    }
+
+    #[test]
+    fn test_step_back_heap_edit() {
+        let mut mem = MemoryHeap::new(16);
+        mem.add("Name? ".as_bytes().to_vec()).unwrap();
+        mem.add("Bob".as_bytes().to_vec()).unwrap();
+        let mut test_vm = VM::new();
+        test_vm.set_history_cap(16);
+        test_vm.io = Box::new(MemoryIo::new(&["Alice"]));
+        test_vm.set_program(vec![22, 0, 0, 0, 1], mem);
+        test_vm.start().unwrap();
+        test_vm.run_once();
+        assert_eq!(
+            test_vm.memory_heap.get(1),
+            Some("Alice".as_bytes().to_vec())
+        );
+        assert!(test_vm.step_back());
+        assert_eq!(test_vm.memory_heap.get(1), Some("Bob".as_bytes().to_vec()));
+    }
+
+    #[test]
+    fn test_step_back_handles() {
+        let mut mem = MemoryHeap::new(1);
+        mem.add(vec![9]).unwrap();
+        let mut test_vm = VM::new();
+        test_vm.set_history_cap(16);
+        test_vm.registers[0] = 3;
+        test_vm.registers[2] = 7;
+        test_vm.registers[3] = 1;
+        // aloc $0, newp $0 $1, stb $2 $1 $3, free $1, hlt
+        test_vm.set_program(vec![16, 0, 65, 0, 1, 71, 2, 1, 3, 66, 1, 0], mem);
+        test_vm.start().unwrap();
+        let original = test_vm.memory_heap.clone();
+        assert_eq!(test_vm.continue_execution(), StepResult::Halted(0));
+        assert_eq!(test_vm.memory_heap.get(1), None);
+
+        assert!(test_vm.step_back());
+        assert!(test_vm.step_back());
+        assert_eq!(test_vm.memory_heap.get(1), Some(vec![0, 7, 0]));
+        assert!(test_vm.step_back());
+        assert_eq!(test_vm.memory_heap.get(1), Some(vec![0, 0, 0]));
+        assert_eq!(test_vm.rewind(0), 2);
+        assert_eq!(test_vm.memory_heap, original);
+    }
+
+    #[test]
+    fn test_history_cap() {
+        // load $0 #1, push $0, inc $0, pop $1, hlt
+        let program = vec![1, 0, 0, 1, 26, 0, 39, 0, 27, 1, 0];
+        let mut test_vm = VM::new();
+        test_vm.set_history_cap(2);
+        test_vm.set_program(program.clone(), MemoryHeap::new(0));
+        test_vm.run();
+        assert_eq!(test_vm.history().len(), 2);
+        assert_eq!(test_vm.rewind(0), 2);
+        assert_eq!(test_vm.executed(), 3);
+        assert_eq!(test_vm.code_offset(), Some(8));
+
+        let mut test_vm = VM::new();
+        test_vm.set_history_cap(0);
+        test_vm.set_program(program, MemoryHeap::new(0));
+        test_vm.run();
+        assert!(test_vm.history().is_empty());
+        assert!(!test_vm.step_back());
+    }
+}
+
+mod profiler {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        assembler::symbols::{Symbol, SymbolTable, SymbolType},
+        vm::profiler::Profile,
+    };
+
+    #[test]
+    fn test_profile_counts() {
+        let mut test_vm = VM::new();
+        test_vm.profile = Some(Profile::default());
+        // main: load $0 #7, call $0, hlt
+        // twice: inc $1, inc $1, ret
+        test_vm.set_program(
+            vec![1, 0, 0, 7, 28, 0, 0, 39, 1, 39, 1, 29],
+            MemoryHeap::new(0),
+        );
+        let mut symbols = SymbolTable::new();
+        symbols.add_symbol(Symbol::new("main".to_string(), SymbolType::Label, 0));
+        symbols.add_symbol(Symbol::new("twice".to_string(), SymbolType::Label, 7));
+        test_vm.run();
+        let profile = test_vm.profile.unwrap();
+        assert_eq!(profile.instructions(), 6);
+        assert_eq!(profile.opcodes[&Opcode::INC].count, 2);
+        assert_eq!(profile.opcodes[&Opcode::CALL].count, 1);
+        assert_eq!(profile.offsets[&9].0, Opcode::INC);
+        assert_eq!(profile.offsets[&9].1.count, 1);
+
+        let labels = profile.labels(Some(&symbols));
+        assert_eq!(labels["main"].count, 3);
+        assert_eq!(labels["twice"].count, 3);
+
+        let report = profile.report(Some(&symbols));
+        assert!(report.starts_with("Executed 6 instruction(s) in "));
+        assert!(report.contains("inc\n"));
+        assert!(report.contains("twice\n"));
+        assert!(report.contains("     9: inc in twice\n"));
+    }
+
+    #[test]
+    fn test_collapsed_stacks() {
+        let mut test_vm = VM::new();
+        test_vm.profile = Some(Profile::default());
+        // main: load $0 #7, call $0, hlt
+        // twice: inc $1, inc $1, ret
+        test_vm.set_program(
+            vec![1, 0, 0, 7, 28, 0, 0, 39, 1, 39, 1, 29],
+            MemoryHeap::new(0),
+        );
+        let mut symbols = SymbolTable::new();
+        symbols.add_symbol(Symbol::new("main".to_string(), SymbolType::Label, 0));
+        symbols.add_symbol(Symbol::new("twice".to_string(), SymbolType::Label, 7));
+        test_vm.run();
+        let collapsed = test_vm.profile.unwrap().collapsed(Some(&symbols));
+        let stacks: Vec<&str> = collapsed
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(
+            stacks,
+            vec![
+                "main;call",
+                "main;hlt",
+                "main;load",
+                "main;twice;inc",
+                "main;twice;ret"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profile_sleep() {
+        let mut test_vm = VM::new();
+        test_vm.profile = Some(Profile::default());
+        test_vm.registers[0] = 20;
+        test_vm.set_program(vec![19, 0], MemoryHeap::new(0));
+        test_vm.run_once();
+        let profile = test_vm.profile.unwrap();
+        assert!(profile.sleeping >= Duration::from_millis(20));
+        assert_eq!(profile.waiting_for_input, Duration::ZERO);
+        assert!(profile.collapsed(None).starts_with("(no label);slp "));
     }
 }
@@ -0,0 +1,168 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use serde_derive::Serialize;
+
+use crate::instruction::{Opcode, Operand};
+
+use super::{PIE_HEADER_LENGTH, VM};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Human,
+    // One JSON object per line
+    Json,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "human" => Ok(TraceFormat::Human),
+            "json" => Ok(TraceFormat::Json),
+            _ => Err(format!("Unknown trace format \"{s}\"")),
+        }
+    }
+}
+
+// Everything one executed instruction did
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceEntry {
+    // Relative to the start of the code, like breakpoints and the disassembly
+    pub offset: usize,
+    pub opcode: String,
+    pub operands: Vec<String>,
+    // New values of the registers the instruction changed
+    pub registers: BTreeMap<usize, i32>,
+    pub float_registers: BTreeMap<usize, f64>,
+    pub equal_flag: bool,
+    // Heap partitions the instruction read or wrote
+    pub heap: Vec<usize>,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>6}: {}", self.offset, self.opcode)?;
+        for operand in &self.operands {
+            write!(f, " {operand}")?;
+        }
+        f.write_str(" ;")?;
+        for (register, value) in &self.registers {
+            write!(f, " ${register} = {value},")?;
+        }
+        for (register, value) in &self.float_registers {
+            write!(f, " $f{register} = {value:?},")?;
+        }
+        write!(f, " equal = {}", self.equal_flag)?;
+        if !self.heap.is_empty() {
+            write!(f, ", heap {:?}", self.heap)?;
+        }
+        Ok(())
+    }
+}
+
+// Writes a trace entry for every instruction the VM executes. Clones share the output.
+#[derive(Clone)]
+pub struct Tracer {
+    pub format: TraceFormat,
+    output: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + Send + 'static>(format: TraceFormat, output: W) -> Self {
+        Self {
+            format,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+
+    pub fn record(&self, entry: &TraceEntry) -> io::Result<()> {
+        let line = match self.format {
+            TraceFormat::Human => entry.to_string(),
+            TraceFormat::Json => serde_json::to_string(entry)?,
+        };
+        writeln!(self.output.lock().unwrap(), "{line}")
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl VM {
+    // Records the instruction at `pc`, which just ran, comparing against the registers it saw
+    pub(super) fn trace(
+        &self,
+        pc: usize,
+        registers: &[i32; 32],
+        float_registers: &[f64; 32],
+        completed: bool,
+    ) {
+        let tracer = match &self.tracer {
+            Some(tracer) => tracer,
+            None => return,
+        };
+        let code = self.program_cursor.get_ref();
+        let opcode = match code.get(pc) {
+            Some(byte) => Opcode::from(*byte),
+            None => return,
+        };
+        let operands = opcode
+            .decode_operands(&mut io::Cursor::new(&code[pc + 1..]))
+            .unwrap_or_default();
+
+        let mut heap: Vec<usize> = operands
+            .iter()
+            .filter_map(|operand| match operand {
+                Operand::HeapIndex(index) => Some(*index as usize),
+                _ => None,
+            })
+            .collect();
+        // handles are read from the registers as they were before the instruction
+        heap.extend(
+            opcode
+                .handle_operands()
+                .iter()
+                .filter_map(|position| VM::partition_operand(operands.get(*position), registers)),
+        );
+        // a new partition's id is only in its register once the instruction completed
+        if completed {
+            heap.extend(opcode.created_handle_operand().and_then(|position| {
+                VM::partition_operand(operands.get(position), &self.registers)
+            }));
+        }
+        heap.sort_unstable();
+        heap.dedup();
+        let code_start = self
+            .get_starting_offset()
+            .map_or(0, |offset| PIE_HEADER_LENGTH + offset);
+
+        let entry = TraceEntry {
+            offset: pc.saturating_sub(code_start),
+            opcode: opcode.mnemonic(),
+            operands: operands.iter().map(|operand| operand.to_string()).collect(),
+            registers: (0..registers.len())
+                .filter(|r| registers[*r] != self.registers[*r])
+                .map(|r| (r, self.registers[r]))
+                .collect(),
+            float_registers: (0..float_registers.len())
+                .filter(|r| float_registers[*r].to_bits() != self.float_registers[*r].to_bits())
+                .map(|r| (r, self.float_registers[r]))
+                .collect(),
+            equal_flag: self.equal_flag,
+            heap,
+        };
+        // a broken trace output should not stop the program
+        tracer.record(&entry).ok();
+    }
+}